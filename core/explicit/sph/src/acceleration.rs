@@ -1,30 +1,38 @@
 use super::sph_utils::{SphDiff, Tensor};
-use nalgebra as na;
 use rayon::prelude::*;
 use utils::{
+    dimension::from_array3,
     error::{SimError, check_nan_to_error},
-    parameters::{DIM, NeighboringList as Neighbor, Particle},
+    parameters::{DIM, NeighboringList as Neighbor, Particle, Vector},
 };
 
+/// dv/dt = div(stress) / rho + g of the `active` particles (all for `None`), the others keep theirs
 pub(crate) fn update_acceleration(
     particles: &mut [Particle<DIM>],
     neighbors: &[Neighbor<DIM>],
     diff_stress: &mut [Tensor<DIM>],
+    gravity: &[f64; 3],
+    active: Option<&[bool]>,
 ) -> Result<(), SimError> {
+    let is_active = |i: usize| active.is_none_or(|active| active[i]);
     let n = particles.len();
 
     // Thread-local buffer for dv/dt
-    let dvdt_buf: Vec<na::Vector3<f64>> = vec![na::Vector3::zeros(); n];
+    let dvdt_buf: Vec<Vector<DIM>> = vec![Vector::<DIM>::zeros(); n];
     let dvdt_buf = std::sync::Arc::new(std::sync::RwLock::new(dvdt_buf));
 
     diff_stress
         .par_iter_mut()
         .enumerate()
         .try_for_each(|(i, stress)| -> Result<(), SimError> {
+            if !is_active(i) {
+                return Ok(());
+            }
+
             // Calculate div(stress)
             stress.sph_div(particles, neighbors, i)?;
 
-            let dvdt = na::Vector3::from(stress.div_tensor) / particles[i].rho;
+            let dvdt = Vector::<DIM>::from(stress.div_tensor) / particles[i].rho + from_array3(gravity);
 
             // store into thread-safe buffer
             {
@@ -38,8 +46,8 @@ pub(crate) fn update_acceleration(
 
     // merge buffer into particles
     #[allow(clippy::unwrap_used, clippy::unwrap_in_result)]
-    for (i, dv) in dvdt_buf.read().unwrap().iter().enumerate() {
-        check_nan_to_error(i, dv.dot(dv))?;
+    for (i, dv) in dvdt_buf.read().unwrap().iter().enumerate().filter(|(i, _)| is_active(*i)) {
+        check_nan_to_error(i, "dvdt", dv.dot(dv))?;
         particles[i].dvdt = *dv;
     }
 
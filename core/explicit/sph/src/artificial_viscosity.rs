@@ -1,44 +1,73 @@
-use nalgebra as na;
+use super::sph_utils::pair_range;
 use rayon::prelude::*;
-use utils::parameters::{DIM, NeighboringList as Neighbor, Particle};
+use utils::parameters::{DIM, Matrix, NeighboringList as Neighbor, Particle, Real};
 
+/// Artificial viscosity added to the stress of both particles of the pairs.
+/// `deterministic`: accumulated in the order of the neighbor table instead of per-thread buffers.
+/// `selected`: only the stress of these particles, from their own pairs (all particles for `None`).
 pub(crate) fn update_artificial_viscosity(
     particles: &mut [Particle<DIM>],
     neighbors: &[Neighbor<DIM>],
-    smooth_length: f64,
     beta: f64,
+    deterministic: bool,
+    selected: Option<&[bool]>,
 ) {
     let n = particles.len();
+    let identity = Matrix::<DIM>::identity();
+    let beta = beta as Real;
+
+    if let Some(selected) = selected {
+        // Every pair is listed from both sides: twice the values of the own pairs
+        let values: Vec<Real> = (0..n)
+            .into_par_iter()
+            .map(|i| {
+                if !selected[i] {
+                    return 0.0;
+                }
+                let pairs = &neighbors[pair_range(particles, i, neighbors.len())];
+                2.0 * pairs
+                    .iter()
+                    .filter_map(|neigh| pair_viscosity(particles, neigh, beta))
+                    .sum::<Real>()
+            })
+            .collect();
+        particles
+            .par_iter_mut()
+            .zip(values.par_iter())
+            .for_each(|(p, value)| p.stress += *value * identity);
+        return;
+    }
+
+    if deterministic {
+        let pair_values: Vec<Option<Real>> = neighbors
+            .par_iter()
+            .map(|neigh| pair_viscosity(particles, neigh, beta))
+            .collect();
+        for (neigh, value) in neighbors.iter().zip(&pair_values) {
+            if let Some(value) = value {
+                particles[neigh.i].stress += *value * identity;
+                particles[neigh.j].stress += *value * identity;
+            }
+        }
+        return;
+    }
 
     // Parallel computation using per-thread buffers (fold + reduce)
     let stress_buf = neighbors
         .par_iter()
         .fold(
-            || vec![na::Matrix3::zeros(); n], // thread-local buffer
+            || vec![Matrix::<DIM>::zeros(); n], // thread-local buffer
             |mut local_buf, neigh| {
-                // Average
-                let rho_ij = 0.5 * (particles[neigh.i].rho + particles[neigh.j].rho);
-                let cij = 0.5 * (particles[neigh.i].sound_v + particles[neigh.j].sound_v);
-
-                // Relative distance
-                let vij = na::Vector3::from(particles[neigh.i].v) - na::Vector3::from(particles[neigh.j].v);
-                let xij = na::Vector3::from(particles[neigh.i].x) - na::Vector3::from(particles[neigh.j].x);
-
-                let v_dot_x = vij.dot(&xij);
-                if v_dot_x < 0.0 {
-                    let coef = v_dot_x / (0.1 * smooth_length).mul_add(0.1 * smooth_length, xij.dot(&xij));
-                    let identity = na::Matrix3::identity();
-                    let coef_val = (-beta * cij).mul_add(coef, beta * coef.powi(2)) / rho_ij;
-
-                    // add stress contributions to local buffer
-                    local_buf[neigh.i] += coef_val * identity;
-                    local_buf[neigh.j] += coef_val * identity;
+                // add stress contributions to local buffer
+                if let Some(value) = pair_viscosity(particles, neigh, beta) {
+                    local_buf[neigh.i] += value * identity;
+                    local_buf[neigh.j] += value * identity;
                 }
                 local_buf
             },
         )
         .reduce(
-            || vec![na::Matrix3::zeros(); n],
+            || vec![Matrix::<DIM>::zeros(); n],
             |mut a, b| {
                 // merge two buffers
                 for i in 0..n {
@@ -53,3 +82,21 @@ pub(crate) fn update_artificial_viscosity(
         particles[i].stress += *s;
     }
 }
+
+/// Artificial viscous pressure of an approaching pair
+fn pair_viscosity(particles: &[Particle<DIM>], neigh: &Neighbor<DIM>, beta: Real) -> Option<Real> {
+    // Average
+    let rho_ij = 0.5 * (particles[neigh.i].rho + particles[neigh.j].rho);
+    let cij = 0.5 * (particles[neigh.i].sound_v + particles[neigh.j].sound_v);
+    let hij = 0.5 * (particles[neigh.i].h + particles[neigh.j].h);
+
+    // Relative distance
+    let vij = particles[neigh.i].v - particles[neigh.j].v;
+    let xij = particles[neigh.i].x - particles[neigh.j].x;
+
+    let v_dot_x = vij.dot(&xij);
+    (v_dot_x < 0.0).then(|| {
+        let coef = v_dot_x / (0.1 * hij).mul_add(0.1 * hij, xij.dot(&xij));
+        (-beta * cij).mul_add(coef, beta * coef.powi(2)) / rho_ij
+    })
+}
@@ -0,0 +1,51 @@
+use super::sph_utils::DIM_F;
+use nalgebra as na;
+use utils::{
+    dimension::to_vector3,
+    parameters::{BodyLoads, DIM, ForceReportConfig, Matrix, NeighboringList as Neighbor, Particle, Real},
+};
+
+/// Pressure and deviatoric (viscous) parts of the Cauchy stress
+fn split_stress(particle: &Particle<DIM>) -> (Real, Matrix<DIM>) {
+    let p = -particle.stress.trace() / DIM_F;
+    (p, particle.stress + p * Matrix::<DIM>::identity())
+}
+
+/// Integrate the pressure and viscous forces exerted by the surrounding fluid on the particles
+/// of `config.region`, using the SPH pair forces across the body surface:
+/// f_ij = m_i m_j (sigma_i / rho_i^2 + sigma_j / rho_j^2) dW_ij
+pub(crate) fn compute_body_loads(
+    particles: &[Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    config: &ForceReportConfig,
+) -> BodyLoads {
+    let in_body: Vec<bool> = particles.iter().map(|p| config.region.contains(p)).collect();
+    let center = na::Vector3::from(config.moment_center);
+
+    let mut pressure = na::Vector3::zeros();
+    let mut viscous = na::Vector3::zeros();
+    let mut moment = na::Vector3::zeros();
+
+    for neigh in neighbors.iter() {
+        let (i, j) = (neigh.i, neigh.j);
+        if !in_body[i] || in_body[j] {
+            continue;
+        }
+
+        let (pi, pj) = (&particles[i], &particles[j]);
+        let mass_ij = pi.rho * pi.volume * pj.rho * pj.volume;
+        let dwdr = neigh.dwdr;
+
+        let (p_i, tau_i) = split_stress(pi);
+        let (p_j, tau_j) = split_stress(pj);
+
+        let f_p = to_vector3(&(-mass_ij * (p_i / pi.rho.powi(2) + p_j / pj.rho.powi(2)) * dwdr));
+        let f_v = to_vector3(&(mass_ij * (tau_i / pi.rho.powi(2) + tau_j / pj.rho.powi(2)) * dwdr));
+
+        pressure += f_p;
+        viscous += f_v;
+        moment += (to_vector3(&pi.x) - center).cross(&(f_p + f_v));
+    }
+
+    BodyLoads::new(pressure, viscous, moment, config)
+}
@@ -1,32 +1,46 @@
 use crate::sph_utils::{SphDiff, Velocity};
 use rayon::prelude::*;
 use utils::{
-    error::SimError,
-    parameters::{DIM, NeighboringList as Neighbor, Particle},
+    error::{SimError, check_nan_to_error},
+    parameters::{DIM, NeighboringList as Neighbor, Particle, Real},
 };
 
-pub(crate) fn update_density(
-    dt: f64,
+/// Continuity equation: d(rho)/dt = -rho div(v) of the `selected` particles (all for `None`)
+pub(crate) fn update_density_rate(
     particles: &mut [Particle<DIM>],
     neighbors: &[Neighbor<DIM>],
     diff_velocity: &mut [Velocity<DIM>],
+    selected: Option<&[bool]>,
 ) -> Result<(), SimError> {
     // Total particles
     let n = particles.len();
+    let is_selected = |i: usize| selected.is_none_or(|selected| selected[i]);
 
     // Calculate div(velocity)
     diff_velocity[..n]
         .par_iter_mut()
         .enumerate()
+        .filter(|(i, _)| is_selected(*i))
         .try_for_each(|(i, v)| v.sph_div(particles, neighbors, i))?;
 
-    // update: rho = -rho * div(velocity) * dt
+    // rate: d(rho)/dt = -rho * div(velocity)
     particles[..n]
         .par_iter_mut()
         .zip(diff_velocity[..n].par_iter())
-        .for_each(|(p, v)| {
-            p.rho += -p.rho * v.div_v * dt;
+        .enumerate()
+        .filter(|(i, _)| is_selected(*i))
+        .for_each(|(_, (p, v))| {
+            p.drhodt = -p.rho * v.div_v;
         });
 
     Ok(())
 }
+
+/// Density update over `dt` with the current rate: rho += d(rho)/dt * dt
+pub(crate) fn update_density(dt: f64, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+    let dt = dt as Real;
+    particles.par_iter_mut().enumerate().try_for_each(|(i, p)| {
+        p.rho = p.drhodt.mul_add(dt, p.rho);
+        check_nan_to_error(i, "rho", p.rho)
+    })
+}
@@ -0,0 +1,163 @@
+use super::neighboring_lists::scaled_kernel;
+use nalgebra as na;
+use rayon::prelude::*;
+use std::f64::consts::{FRAC_PI_4, SQRT_2};
+use utils::parameters::{DIM, FreeSurfaceConfig, Matrix, NeighboringList as Neighbor, Particle, Real, Vector};
+
+/// Classification of a particle by the eigenvalue and divergence tests
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SurfaceTest {
+    Interior,
+    Surface,
+    /// Decided by the scan of the region along the normal
+    Candidate,
+}
+
+/// Gradient at x_i - x_j of the solver pair kernel: the average of `scaled_kernel` with h_i and h_j
+fn kernel_gradient(pi: &Particle<DIM>, pj: &Particle<DIM>) -> Vector<DIM> {
+    let xij = pi.x - pj.x;
+    let d = xij.norm();
+    if d <= 0.0 {
+        return Vector::<DIM>::zeros();
+    }
+    let dwdr = 0.5 * (scaled_kernel(d, pi.h).1 + scaled_kernel(d, pj.h).1);
+    dwdr * xij / d
+}
+
+/// Detect the free-surface particles and their normals (Marrone et al. 2010).
+///
+/// B_i = sum_j V_j (x_j - x_i) (x) grad W_ij gives div(r) = tr(B_i) and the smallest eigenvalue lambda_i,
+/// the normal is n_i = -B_i^-1 sum_j V_j grad W_ij. Candidates are on the surface unless a neighbor
+/// occupies the cone of half-angle pi/4 along n_i (|x_ji| < sqrt(2) h) or the ball of radius h around x_i + h n_i.
+pub(crate) fn detect_free_surface(particles: &mut [Particle<DIM>], neighbors: &[Neighbor<DIM>], config: &FreeSurfaceConfig) {
+    let n = particles.len();
+
+    // Renormalization matrix and kernel gradient sum
+    let mut b = vec![Matrix::<DIM>::zeros(); n];
+    let mut grad = vec![Vector::<DIM>::zeros(); n];
+    for neigh in neighbors.iter().filter(|neigh| neigh.i != neigh.j) {
+        let (pi, pj) = (&particles[neigh.i], &particles[neigh.j]);
+        if pi.is_rigid() {
+            continue;
+        }
+        let xij = pi.x - pj.x;
+        let dw = kernel_gradient(pi, pj);
+        b[neigh.i] += pj.volume * (-xij) * dw.transpose();
+        grad[neigh.i] += pj.volume * dw;
+    }
+
+    // Eigenvalue/divergence test and normal
+    let (lambda_surface, lambda_interior) = (config.lambda_surface as Real, config.lambda_interior as Real);
+    let div_r_threshold = config.div_r_threshold as Real;
+    let tests: Vec<(SurfaceTest, Vector<DIM>)> = b
+        .par_iter()
+        .zip(grad.par_iter())
+        .zip(particles.par_iter())
+        .map(|((b, grad), p)| {
+            if p.is_rigid() {
+                return (SurfaceTest::Interior, Vector::<DIM>::zeros());
+            }
+            let lambda = na::SymmetricEigen::new(0.5 * (b + b.transpose())).eigenvalues.min();
+            let test = if lambda <= lambda_surface {
+                SurfaceTest::Surface
+            } else if lambda > lambda_interior && b.trace() >= div_r_threshold {
+                SurfaceTest::Interior
+            } else {
+                SurfaceTest::Candidate
+            };
+            if test == SurfaceTest::Interior {
+                return (test, Vector::<DIM>::zeros());
+            }
+            let normal = -b.try_inverse().map_or(*grad, |l| l * grad);
+            (test, normal.try_normalize(Real::EPSILON).unwrap_or_else(Vector::<DIM>::zeros))
+        })
+        .collect();
+
+    // Scan region of the candidates
+    let mut occupied = vec![false; n];
+    for neigh in neighbors.iter().filter(|neigh| neigh.i != neigh.j) {
+        let (test, normal) = &tests[neigh.i];
+        if *test != SurfaceTest::Candidate || occupied[neigh.i] {
+            continue;
+        }
+        let h = particles[neigh.i].h;
+        let xji = particles[neigh.j].x - particles[neigh.i].x;
+        let d = xji.norm();
+        occupied[neigh.i] = if d < SQRT_2 as Real * h {
+            d > 0.0 && (normal.dot(&xji) / d).clamp(-1.0, 1.0).acos() < FRAC_PI_4 as Real
+        } else {
+            (xji - h * normal).norm() < h
+        };
+    }
+
+    particles
+        .par_iter_mut()
+        .zip(tests.par_iter().zip(occupied.par_iter()))
+        .for_each(|(p, ((test, normal), occupied))| {
+            p.surface = match test {
+                SurfaceTest::Interior => false,
+                SurfaceTest::Surface => true,
+                SurfaceTest::Candidate => !occupied,
+            };
+            let normal = if p.surface { *normal } else { Vector::<DIM>::zeros() };
+            p.normal = normal;
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighboring_lists::search_near_particles;
+    use utils::materials::Material;
+
+    const DX: Real = 0.01;
+    const SIDE: usize = 8;
+
+    /// Is the lattice index on the outer layer of the block
+    fn on_boundary(index: [usize; DIM]) -> bool {
+        index.iter().any(|&k| k == 0 || k == SIDE - 1)
+    }
+
+    /// Only the outer layer of a block is on the surface, with outward unit normals
+    #[test]
+    fn test_block_surface() {
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let indices: Vec<[usize; DIM]> = (0..SIDE.pow(DIM as u32))
+            .map(|index| std::array::from_fn(|d| index / SIDE.pow(d as u32) % SIDE))
+            .collect();
+        let mut particles: Vec<Particle<DIM>> = indices
+            .iter()
+            .map(|index| {
+                let mut p = template.clone();
+                #[allow(clippy::cast_precision_loss)]
+                for (x, &k) in p.x.iter_mut().zip(index) {
+                    *x = k as Real * DX;
+                }
+                p.volume = DX.powi(DIM as i32);
+                p.h = 1.2 * DX;
+                p
+            })
+            .collect();
+        let max_pairs = particles.len() * 100;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = search_near_particles(&mut particles, &mut neighbors, max_pairs, 0.012, 2.0).expect("neighbor search");
+        detect_free_surface(&mut particles, &neighbors[0..k], &FreeSurfaceConfig::default());
+
+        #[allow(clippy::cast_precision_loss)]
+        let center = Vector::<DIM>::repeat(0.5 * (SIDE - 1) as Real * DX);
+        for (p, &index) in particles.iter().zip(&indices) {
+            assert_eq!(p.surface, on_boundary(index), "{index:?}");
+            if p.surface {
+                assert!((p.normal.norm() - 1.0).abs() < 1.0e-4);
+                assert!(p.normal.dot(&(p.x - center)) > 0.0, "{index:?}: {:?}", p.normal);
+            } else {
+                assert!(p.normal.norm() == 0.0);
+            }
+        }
+
+        // Middle of the x = 0 face: normal along -x
+        let face: [usize; DIM] = std::array::from_fn(|d| if d == 0 { 0 } else { SIDE / 2 });
+        let p = &particles[indices.iter().position(|&index| index == face).expect("face particle")];
+        assert!(p.normal[0] < -0.99, "{:?}", p.normal);
+    }
+}
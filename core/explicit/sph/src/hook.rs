@@ -0,0 +1,122 @@
+//! Per-step custom logic injected into [`Simulation`](crate::simulation::Simulation).
+//!
+//! Callbacks in the order of a step:
+//! `before_boundary_conditions` -> `after_boundary_conditions` -> `after_forces` (every force evaluation
+//! of the integrator) -> `after_integration` -> `on_output` (output steps).
+//! `on_finish` ends a completed run and `on_error` a failed step.
+use super::rates::{Rates, SphRates};
+use utils::{
+    error::SimError,
+    parameters::{CheckpointConfig, DIM, NeighboringList as Neighbor, Particle, RigidBody},
+};
+
+/// State of the simulation passed to the hooks
+pub struct HookContext<'a> {
+    /// Current step number
+    pub step: usize,
+    /// Time [s] (of the force evaluation for `after_forces`)
+    pub time: f64,
+    /// Time step [s]
+    pub dt: f64,
+    pub particles: &'a mut [Particle<DIM>],
+    pub neighbors: &'a [Neighbor<DIM>],
+    pub bodies: &'a mut [RigidBody],
+    pub config: &'a CheckpointConfig,
+    /// Particles whose rates were evaluated in `after_forces` (`None`: all)
+    pub active: Option<&'a [bool]>,
+}
+
+/// Custom logic at well-defined points of the simulation loop.
+/// All callbacks default to doing nothing; an error stops the simulation.
+pub trait SimulationHook: Send {
+    /// Before the boundary conditions of a step (after the time-step control)
+    /// # Errors
+    /// Stops the simulation
+    fn before_boundary_conditions(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// After the boundary conditions of a step
+    /// # Errors
+    /// Stops the simulation
+    fn after_boundary_conditions(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// After each evaluation of `dvdt` and `dedt` (custom forcing is added here)
+    /// # Errors
+    /// Stops the simulation
+    fn after_forces(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// After the particles and bodies are advanced over the step
+    /// # Errors
+    /// Stops the simulation
+    fn after_integration(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// At an output step, before the outputs and the checkpoint are written
+    /// # Errors
+    /// Stops the simulation
+    fn on_output(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// After the last step of a run
+    /// # Errors
+    /// Stops the simulation
+    fn on_finish(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// A step failed with `error`
+    fn on_error(&mut self, _error: &SimError, _context: &mut HookContext<'_>) {}
+}
+
+/// SPH rates followed by the `after_forces` hooks
+pub(crate) struct HookedRates<'a> {
+    pub(crate) rates: SphRates<'a>,
+    pub(crate) hooks: &'a mut [Box<dyn SimulationHook>],
+    pub(crate) bodies: &'a mut [RigidBody],
+    pub(crate) config: &'a CheckpointConfig,
+    pub(crate) step: usize,
+    pub(crate) dt: f64,
+}
+
+impl HookedRates<'_> {
+    fn after_forces(&mut self, particles: &mut [Particle<DIM>], time: f64, active: Option<&[bool]>) -> Result<(), SimError> {
+        let mut context = HookContext {
+            step: self.step,
+            time,
+            dt: self.dt,
+            particles,
+            neighbors: self.rates.neighbors,
+            bodies: self.bodies,
+            config: self.config,
+            active,
+        };
+        self.hooks.iter_mut().try_for_each(|hook| hook.after_forces(&mut context))
+    }
+}
+
+impl Rates for HookedRates<'_> {
+    fn continuity(&mut self, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+        self.rates.continuity(particles)
+    }
+
+    fn active_continuity(&mut self, particles: &mut [Particle<DIM>], active: &[bool]) -> Result<(), SimError> {
+        self.rates.active_continuity(particles, active)
+    }
+
+    fn forces(&mut self, particles: &mut [Particle<DIM>], time: f64) -> Result<(), SimError> {
+        self.rates.forces(particles, time)?;
+        self.after_forces(particles, time, None)
+    }
+
+    fn active_forces(&mut self, particles: &mut [Particle<DIM>], time: f64, active: &[bool]) -> Result<(), SimError> {
+        self.rates.active_forces(particles, time, active)?;
+        self.after_forces(particles, time, Some(active))
+    }
+}
@@ -0,0 +1,493 @@
+use super::{
+    density::update_density,
+    rates::Rates,
+    velocity::{update_location, update_velocity},
+};
+use nalgebra as na;
+use rayon::prelude::*;
+use utils::{
+    cfl_condition::particle_dt,
+    error::{SimError, check_nan_to_error},
+    parameters::{DIM, IntegratorKind, Particle, Real, TimeStepConfig},
+};
+
+/// Advance of the evolved particle fields (x, v, rho, e) over one time step.
+/// Rigid particles keep their position and velocity (moved by the rigid bodies).
+pub(crate) trait Integrator {
+    /// Advance the particles over `dt` from `time`
+    /// # Errors
+    /// Nan value occurs
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError>;
+}
+
+/// Integrator of the configured scheme (kick-drift-kick with individual time steps for several levels)
+pub(crate) fn new_integrator(kind: IntegratorKind, time_step: &TimeStepConfig) -> Box<dyn Integrator> {
+    if time_step.levels > 1 {
+        return Box::new(HierarchicalVerlet {
+            config: time_step.clone(),
+            levels: Vec::new(),
+        });
+    }
+    match kind {
+        IntegratorKind::VelocityVerlet => Box::new(VelocityVerlet),
+        IntegratorKind::PositionVerlet => Box::new(PositionVerlet),
+        IntegratorKind::PredictorCorrector => Box::new(PredictorCorrector::default()),
+        IntegratorKind::RungeKutta2 => Box::new(RungeKutta2::default()),
+        IntegratorKind::RungeKutta4 => Box::new(RungeKutta4::default()),
+    }
+}
+
+/// Evolved fields of a particle, also used for their time derivatives (in `f64` over the stages of a step)
+#[derive(Clone, Copy)]
+struct Fields {
+    x: na::SVector<f64, DIM>,
+    v: na::SVector<f64, DIM>,
+    rho: f64,
+    e: f64,
+}
+
+impl Fields {
+    fn zeros() -> Self {
+        Self {
+            x: na::SVector::<f64, DIM>::zeros(),
+            v: na::SVector::<f64, DIM>::zeros(),
+            rho: 0.0,
+            e: 0.0,
+        }
+    }
+
+    fn of(p: &Particle<DIM>) -> Self {
+        Self {
+            x: p.x.cast(),
+            v: p.v.cast(),
+            rho: f64::from(p.rho),
+            e: f64::from(p.e),
+        }
+    }
+
+    /// Time derivatives (v, dv/dt, d(rho)/dt, de/dt)
+    fn rates_of(p: &Particle<DIM>) -> Self {
+        Self {
+            x: p.v.cast(),
+            v: p.dvdt.cast(),
+            rho: f64::from(p.drhodt),
+            e: f64::from(p.dedt),
+        }
+    }
+
+    /// self + h * rate
+    fn add(&self, h: f64, rate: &Self) -> Self {
+        Self {
+            x: self.x + h * rate.x,
+            v: self.v + h * rate.v,
+            rho: h.mul_add(rate.rho, self.rho),
+            e: h.mul_add(rate.e, self.e),
+        }
+    }
+
+    /// Set the fields of particle `i`
+    /// # Errors
+    /// Nan value occurs
+    fn store(&self, i: usize, p: &mut Particle<DIM>) -> Result<(), SimError> {
+        p.rho = self.rho as Real;
+        check_nan_to_error(i, "rho", p.rho)?;
+        if p.is_rigid() {
+            return Ok(());
+        }
+        p.x = self.x.cast();
+        p.v = self.v.cast();
+        p.e = self.e as Real;
+        for d in 0..DIM {
+            check_nan_to_error(i, "x", p.x[d])?;
+            check_nan_to_error(i, "v", p.v[d])?;
+        }
+        Ok(())
+    }
+}
+
+/// Fields of all particles at the beginning of the step
+fn save(particles: &[Particle<DIM>], base: &mut Vec<Fields>) {
+    base.clear();
+    base.par_extend(particles.par_iter().map(Fields::of));
+}
+
+/// Set the particles to `base + h * rates` with the current rates.
+/// # Errors
+/// Nan value occurs
+fn advance_from(particles: &mut [Particle<DIM>], base: &[Fields], h: f64) -> Result<(), SimError> {
+    particles
+        .par_iter_mut()
+        .zip(base.par_iter())
+        .enumerate()
+        .try_for_each(|(i, (p, base))| base.add(h, &Fields::rates_of(p)).store(i, p))
+}
+
+/// Kick-drift-kick: the density follows the drift with the half-step velocity
+struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        update_velocity(0.5 * dt, particles)?;
+        update_location(dt, particles)?;
+        rates.continuity(particles)?;
+        update_density(dt, particles)?;
+        rates.forces(particles, time + dt)?;
+        update_velocity(0.5 * dt, particles)
+    }
+}
+
+/// Drift-kick-drift (Leimkuhler): x and rho at the half step with the old rates,
+/// the kick with the mid-step forces, the second half with the new velocity.
+struct PositionVerlet;
+
+impl Integrator for PositionVerlet {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        update_location(0.5 * dt, particles)?;
+        update_density(0.5 * dt, particles)?;
+        rates.forces(particles, 0.5_f64.mul_add(dt, time))?;
+        update_velocity(dt, particles)?;
+        update_location(0.5 * dt, particles)?;
+        rates.continuity(particles)?;
+        update_density(0.5 * dt, particles)
+    }
+}
+
+/// Monaghan (1989): prediction to the half step with the old rates, correction
+/// with the mid-step rates and extrapolation phi^(n+1) = 2 phi^(n+1/2) - phi^n.
+#[derive(Default)]
+struct PredictorCorrector {
+    base: Vec<Fields>,
+}
+
+impl Integrator for PredictorCorrector {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        save(particles, &mut self.base);
+        advance_from(particles, &self.base, 0.5 * dt)?;
+        rates.evaluate(particles, 0.5_f64.mul_add(dt, time))?;
+
+        particles
+            .par_iter_mut()
+            .zip(self.base.par_iter())
+            .enumerate()
+            .try_for_each(|(i, (p, base))| {
+                let mut rate = Fields::rates_of(p);
+                // Corrected mid-step velocity moves the particle
+                rate.x = base.v + 0.5 * dt * rate.v;
+                base.add(dt, &rate).store(i, p)
+            })
+    }
+}
+
+/// Explicit midpoint rule
+#[derive(Default)]
+struct RungeKutta2 {
+    base: Vec<Fields>,
+}
+
+impl Integrator for RungeKutta2 {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        save(particles, &mut self.base);
+        rates.evaluate(particles, time)?;
+        advance_from(particles, &self.base, 0.5 * dt)?;
+        rates.evaluate(particles, 0.5_f64.mul_add(dt, time))?;
+        advance_from(particles, &self.base, dt)
+    }
+}
+
+/// Classical fourth-order Runge-Kutta
+#[derive(Default)]
+struct RungeKutta4 {
+    base: Vec<Fields>,
+    /// Weighted sum of the stage rates
+    sum: Vec<Fields>,
+}
+
+impl RungeKutta4 {
+    /// Add the rates of the current stage with `weight`
+    fn accumulate(&mut self, weight: f64, particles: &[Particle<DIM>]) {
+        self.sum
+            .par_iter_mut()
+            .zip(particles.par_iter())
+            .for_each(|(sum, p)| *sum = sum.add(weight, &Fields::rates_of(p)));
+    }
+}
+
+impl Integrator for RungeKutta4 {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        save(particles, &mut self.base);
+        self.sum.clear();
+        self.sum.resize(particles.len(), Fields::zeros());
+
+        let stages = [(0.0, 1.0 / 6.0), (0.5, 1.0 / 3.0), (0.5, 1.0 / 3.0), (1.0, 1.0 / 6.0)];
+        for (s, (c, weight)) in stages.into_iter().enumerate() {
+            if s > 0 {
+                advance_from(particles, &self.base, c * dt)?;
+            }
+            rates.evaluate(particles, c.mul_add(dt, time))?;
+            self.accumulate(weight, particles);
+        }
+
+        particles
+            .par_iter_mut()
+            .zip(self.base.par_iter().zip(self.sum.par_iter()))
+            .enumerate()
+            .try_for_each(|(i, (p, (base, sum)))| base.add(dt, sum).store(i, p))
+    }
+}
+
+/// Kick-drift-kick with individual time steps on power-of-two levels.
+///
+/// Each particle is binned into the coarsest level l whose step dt / 2^l resolves its own stability limit.
+/// All particles drift with the finest substep, only the particles completing their step get new rates
+/// and the closing kick; the others keep their rates. The levels are synchronized at the end of every `dt`.
+struct HierarchicalVerlet {
+    config: TimeStepConfig,
+    /// Time-step level of the particles
+    levels: Vec<usize>,
+}
+
+impl HierarchicalVerlet {
+    /// Bin the particles into the levels of the time step `dt`
+    fn assign_levels(&mut self, dt: f64, particles: &[Particle<DIM>]) {
+        let finest = self.config.levels - 1;
+        self.levels.clear();
+        self.levels.par_extend(particles.par_iter().map(|p| {
+            // Rigid particles only contribute the fluid force at the end of the step
+            if p.is_rigid() || p.h <= 0.0 {
+                return 0;
+            }
+            let (limit, _) = particle_dt(p, &self.config);
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let level = (dt / limit).log2().ceil().max(0.0) as usize;
+            level.min(finest)
+        }));
+    }
+
+    /// Half kick of the `active` particles over their own time step: v += dt_l / 2 dv/dt, e += dt_l / 2 de/dt
+    fn half_kick(&self, delta: f64, particles: &mut [Particle<DIM>], active: &[bool]) -> Result<(), SimError> {
+        let substeps = self.config.substeps();
+        particles
+            .par_iter_mut()
+            .zip(self.levels.par_iter().zip(active.par_iter()))
+            .enumerate()
+            .filter(|(_, (p, (_, active)))| **active && !p.is_rigid())
+            .try_for_each(|(i, (p, (level, _)))| {
+                #[allow(clippy::cast_precision_loss)]
+                let h = (0.5 * delta * (substeps >> level) as f64) as Real;
+                p.v += h * p.dvdt;
+                p.e = h.mul_add(p.dedt, p.e);
+                (0..DIM).try_for_each(|d| check_nan_to_error(i, "v", p.v[d]))
+            })
+    }
+}
+
+impl Integrator for HierarchicalVerlet {
+    fn step(&mut self, dt: f64, time: f64, particles: &mut [Particle<DIM>], rates: &mut dyn Rates) -> Result<(), SimError> {
+        let substeps = self.config.substeps();
+        #[allow(clippy::cast_precision_loss)]
+        let delta = dt / substeps as f64;
+        self.assign_levels(dt, particles);
+
+        // All levels start together
+        let mut active = vec![true; particles.len()];
+        for s in 0..substeps {
+            self.half_kick(delta, particles, &active)?;
+            update_location(delta, particles)?;
+
+            // Particles completing their step: new rates and closing kick
+            active
+                .par_iter_mut()
+                .zip(self.levels.par_iter())
+                .for_each(|(active, &level)| *active = (s + 1).is_multiple_of(substeps >> level));
+            rates.active_continuity(particles, &active)?;
+            update_density(delta, particles)?;
+            #[allow(clippy::cast_precision_loss)]
+            let t = delta.mul_add((s + 1) as f64, time);
+            rates.active_forces(particles, t, &active)?;
+            self.half_kick(delta, particles, &active)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+    use utils::materials::Material;
+
+    /// Harmonic oscillator dv/dt = -x with a density following d(rho)/dt = -rho v_x
+    struct Oscillator;
+
+    impl Rates for Oscillator {
+        fn continuity(&mut self, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+            for p in particles {
+                p.drhodt = -p.rho * p.v[0];
+            }
+            Ok(())
+        }
+
+        fn forces(&mut self, particles: &mut [Particle<DIM>], _time: f64) -> Result<(), SimError> {
+            // Stiffness omega^2 in the tag
+            for p in particles {
+                #[allow(clippy::cast_precision_loss)]
+                let stiffness = p.tag.max(1) as Real;
+                p.dvdt = -stiffness * p.x;
+            }
+            Ok(())
+        }
+    }
+
+    /// Oscillator counting the rate evaluations of each particle
+    #[derive(Default)]
+    struct Counted {
+        continuity: Vec<usize>,
+        forces: Vec<usize>,
+    }
+
+    impl Counted {
+        fn count(counts: &mut Vec<usize>, active: &[bool]) {
+            counts.resize(active.len(), 0);
+            for (count, _) in counts.iter_mut().zip(active).filter(|(_, active)| **active) {
+                *count += 1;
+            }
+        }
+    }
+
+    impl Rates for Counted {
+        fn continuity(&mut self, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+            Oscillator.continuity(particles)
+        }
+
+        fn active_continuity(&mut self, particles: &mut [Particle<DIM>], active: &[bool]) -> Result<(), SimError> {
+            Self::count(&mut self.continuity, active);
+            Oscillator.active_continuity(particles, active)
+        }
+
+        fn forces(&mut self, particles: &mut [Particle<DIM>], time: f64) -> Result<(), SimError> {
+            Oscillator.forces(particles, time)
+        }
+
+        fn active_forces(&mut self, particles: &mut [Particle<DIM>], time: f64, active: &[bool]) -> Result<(), SimError> {
+            Self::count(&mut self.forces, active);
+            Oscillator.active_forces(particles, time, active)
+        }
+    }
+
+    /// All schemes return the oscillator to its initial state after one period
+    #[test]
+    fn test_integrators_oscillator() {
+        let kinds = [
+            (IntegratorKind::VelocityVerlet, 1.0e-3),
+            (IntegratorKind::PositionVerlet, 1.0e-3),
+            (IntegratorKind::PredictorCorrector, 1.0e-3),
+            (IntegratorKind::RungeKutta2, 1.0e-3),
+            (IntegratorKind::RungeKutta4, 1.0e-8_f64),
+        ];
+        let steps = 1000;
+        #[allow(clippy::cast_precision_loss)]
+        let dt = 2.0 * PI / steps as f64;
+
+        for (kind, tolerance) in kinds {
+            // Round-off of the stored state in single precision
+            let tolerance = tolerance.max(1.0e3 * f64::from(Real::EPSILON));
+            let mut particles = vec![Particle::<DIM>::new(0, &Material::water(), 293.15)];
+            particles[0].x[0] = 1.0;
+            particles[0].rho = 1.0;
+            let mut rates = Oscillator;
+            let mut integrator = new_integrator(kind, &TimeStepConfig::default());
+            rates.evaluate(&mut particles, 0.0).expect("initial rates");
+            for step in 0..steps {
+                #[allow(clippy::cast_precision_loss)]
+                let time = step as f64 * dt;
+                integrator.step(dt, time, &mut particles, &mut rates).expect("step");
+            }
+
+            // rho = exp(x(0) - x(t)) returns to 1
+            let p = &particles[0];
+            assert!(f64::from(p.x[0] - 1.0).abs() < tolerance, "{kind:?}: x = {}", p.x[0]);
+            assert!(f64::from(p.v[0]).abs() < tolerance, "{kind:?}: v = {}", p.v[0]);
+            assert!(f64::from(p.rho - 1.0).abs() < 10.0 * tolerance, "{kind:?}: rho = {}", p.rho);
+        }
+    }
+
+    /// A particle of level l has its rates evaluated 2^l times per step, the others keep theirs
+    #[test]
+    fn test_hierarchical_evaluations() {
+        let mut particle = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        particle.x[0] = 1.0;
+        particle.h = 1.0e-3;
+        particle.sound_v = 0.0;
+        particle.viscosity = 0.0;
+        let mut stiff = particle.clone();
+        stiff.tag = 64;
+        let mut particles = vec![particle, stiff];
+
+        let config = TimeStepConfig {
+            levels: 4,
+            ..Default::default()
+        };
+        let mut rates = Counted::default();
+        let mut integrator = HierarchicalVerlet {
+            config,
+            levels: Vec::new(),
+        };
+        rates.evaluate(&mut particles, 0.0).expect("initial rates");
+
+        let dt = 2.0e-3;
+        let mut expected = [0; 2];
+        for step in 0..100 {
+            let time = f64::from(step) * dt;
+            integrator.step(dt, time, &mut particles, &mut rates).expect("step");
+            for (expected, level) in expected.iter_mut().zip(&integrator.levels) {
+                *expected += 1 << level;
+            }
+        }
+        assert_eq!(rates.forces, expected);
+        assert_eq!(rates.continuity, expected);
+        assert!(expected[1] > expected[0], "evaluations = {expected:?}");
+    }
+
+    /// A stiff and a soft oscillator on different levels both complete their periods
+    #[test]
+    fn test_hierarchical_time_steps() {
+        let mut particle = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        particle.x[0] = 1.0;
+        particle.h = 1.0e-3;
+        particle.sound_v = 0.0;
+        particle.viscosity = 0.0;
+        let mut stiff = particle.clone();
+        stiff.tag = 64;
+        let mut particles = vec![particle, stiff];
+
+        let config = TimeStepConfig {
+            levels: 4,
+            ..Default::default()
+        };
+        let mut rates = Oscillator;
+        let mut integrator = HierarchicalVerlet {
+            config,
+            levels: Vec::new(),
+        };
+        rates.evaluate(&mut particles, 0.0).expect("initial rates");
+
+        // One period of the soft oscillator is 8 periods of the stiff one
+        let steps = 2000;
+        #[allow(clippy::cast_precision_loss)]
+        let dt = 2.0 * PI / steps as f64;
+        for step in 0..steps {
+            #[allow(clippy::cast_precision_loss)]
+            let time = step as f64 * dt;
+            integrator.step(dt, time, &mut particles, &mut rates).expect("step");
+        }
+        assert!(
+            integrator.levels[1] > integrator.levels[0],
+            "levels = {:?}",
+            integrator.levels
+        );
+        for p in &particles {
+            assert!((p.x[0] - 1.0).abs() < 1.0e-2, "x = {}", p.x[0]);
+            assert!(p.v[0].abs() < 1.0e-1, "v = {}", p.v[0]);
+        }
+    }
+}
@@ -1,8 +1,26 @@
+// `f64::from` widens `Real`, the same type in double precision
+#![cfg_attr(not(feature = "f32"), allow(clippy::useless_conversion))]
+
 mod acceleration;
 mod artificial_viscosity;
+mod body_force;
 mod density;
+mod free_surface;
+pub mod hook;
+mod integrator;
 mod neighboring_lists;
+mod open_boundary;
+mod particle_pool;
+mod porous;
+mod rates;
+mod refinement;
+mod rigid_body;
+mod rotating_frame;
+mod scalar_transport;
+pub mod simulation;
 mod smoothing;
+mod smoothing_length;
+mod solid;
 pub mod sph;
 mod sph_utils;
 mod stress;
@@ -1,63 +1,84 @@
+use super::sph_utils::DIM_F;
 use nalgebra as na;
-use nalgebra::SimdComplexField;
 use std::collections::HashMap;
 use utils::{
     error::{FailedWriteFileSnafu, SimError},
-    parameters::{DIM, NeighboringList as Neighbor, Particle},
+    kernel::cubic_spline,
+    parameters::{DIM, NeighboringList as Neighbor, Particle, Real, Vector},
 };
 
-// Kernel function
-fn b_spline_kernel(q: f64) -> (f64, f64) {
-    match q {
-        0.0..=1.0 => {
-            let q2 = q.simd_powf(2.0);
-            let w = (0.75 * q2).mul_add(q, 1.5_f64.mul_add(-q2, 1.0));
-            let dwdq = (-3.0_f64).mul_add(q, 2.25 * q2);
-            (w, dwdq)
-        }
-        1.0..=2.0 => {
-            let q3 = (2.0 - q).simd_powf(3.0);
-            let w = 0.25 * q3;
-            let dwdq = -0.75 * q3;
-            (w, dwdq)
-        }
-        _ => (0.0, 0.0),
+/// Normalized cubic spline kernel and its radial derivative dW/dr at distance `d` with smoothing length `h`
+pub(crate) fn scaled_kernel(d: Real, h: Real) -> (Real, Real) {
+    let (w, dwdr) = cubic_spline(f64::from(d), f64::from(h));
+    (w as Real, dwdr as Real)
+}
+
+/// Derivative dW/dh = -(DIM W + d dW/dd) / h of `scaled_kernel` (W = sigma b(d / h) / h^DIM)
+pub(crate) fn kernel_dh(d: Real, h: Real) -> Real {
+    let (w, dwdr) = scaled_kernel(d, h);
+    -DIM_F.mul_add(w, d * dwdr) / h
+}
+
+/// Symmetric pair kernel: average of the kernels with h_i and h_j (each gradient divided by the grad-h term)
+pub(crate) fn pair_kernel(particles: &[Particle<DIM>], i: usize, j: usize) -> (Real, Vector<DIM>) {
+    let (pi, pj) = (&particles[i], &particles[j]);
+    let d = pi.x.metric_distance(&pj.x);
+
+    let (wi, dwdr_i) = scaled_kernel(d, pi.h);
+    let (wj, dwdr_j) = scaled_kernel(d, pj.h);
+    let dwdr = 0.5 * (dwdr_i / pi.omega + dwdr_j / pj.omega);
+
+    // multiply dwdr by base vector: ei = x/r
+    let mut grad = na::Vector::from([dwdr; DIM]);
+    for d_ in 0..DIM {
+        grad[d_] *= pi.x[d_] / d;
     }
+    (0.5 * (wi + wj), grad)
 }
 
-type Grid = HashMap<(usize, usize, usize), Vec<usize>>;
+type Grid = HashMap<[isize; DIM], Vec<usize>>;
+
+/// Number of cells searched around a cell (itself included)
+const NEIGHBOR_CELLS: usize = 3_usize.pow(DIM as u32);
+
+fn min_location(particles: &[Particle<DIM>], index: usize) -> Real {
+    particles.iter().map(|p| p.x[index]).fold(Real::INFINITY, Real::min)
+}
 
-fn min_location(particles: &[Particle<DIM>], index: usize) -> f64 {
-    particles.iter().map(|p| p.x[index]).fold(f64::INFINITY, f64::min)
+fn cell_location(x: &Vector<DIM>, min: &[Real; DIM], cell_size: Real) -> [isize; DIM] {
+    std::array::from_fn(|d| ((x[d] - min[d]) / cell_size).floor() as isize)
 }
 
-fn cell_location(x: f64, min: f64, cell_size: f64) -> usize {
-    ((x - min) / cell_size).floor() as usize
+/// `k`-th of the 3^DIM cells around `cell`, the last axis running fastest
+fn neighbor_cell(cell: &[isize; DIM], k: usize) -> [isize; DIM] {
+    let mut neighbor = *cell;
+    let mut k = k;
+    for d in (0..DIM).rev() {
+        neighbor[d] += (k % 3) as isize - 1;
+        k /= 3;
+    }
+    neighbor
 }
 
-fn cll_property(particles: &[Particle<DIM>], cell_size: f64) -> (f64, f64, f64, Grid) {
+fn cll_property(particles: &[Particle<DIM>], cell_size: Real) -> ([Real; DIM], Grid) {
     // HashMap for cell layout
     let mut grid: Grid = HashMap::new();
 
     // Calculate the minimum coordinates
-    let min_x = min_location(particles, 0);
-    let min_y = min_location(particles, 1);
-    let min_z = min_location(particles, 2);
+    let min = std::array::from_fn(|d| min_location(particles, d));
 
     // Place particles into cells
     for (i, particle) in particles.iter().enumerate() {
         // Subtract the minimum coordinates and divide by cell size to calculate cell index
-        let cell_x = cell_location(particle.x[0], min_x, cell_size);
-        let cell_y = cell_location(particle.x[1], min_y, cell_size);
-        let cell_z = cell_location(particle.x[2], min_z, cell_size);
-
-        grid.entry((cell_x, cell_y, cell_z)).or_default().push(i);
+        grid.entry(cell_location(&particle.x, &min, cell_size)).or_default().push(i);
     }
 
-    (min_x, min_y, min_z, grid)
+    (min, grid)
 }
 
 // Searching
+// Variable smoothing length: pairs interact within the larger support 2 max(h_i, h_j)
+// with the symmetric (averaged) kernel of `pair_kernel`.
 pub(crate) fn search_near_particles(
     particles: &mut [Particle<DIM>],
     neigh_lists: &mut [Neighbor<DIM>],
@@ -65,59 +86,42 @@ pub(crate) fn search_near_particles(
     smooth_length: f64,
     cell_scale: f64,
 ) -> Result<usize, SimError> {
-    let smooth_length_squared = (2.0 * smooth_length).simd_powf(2.0);
-    let cell_size = cell_scale * smooth_length;
-    let (min_x, min_y, min_z, grid) = cll_property(particles, cell_size);
+    let smooth_length = smooth_length as Real;
+    let h_max = particles.iter().map(|p| p.h).fold(smooth_length, Real::max);
+    let cell_size = cell_scale as Real * h_max;
+    let (min, grid) = cll_property(particles, cell_size);
 
     // i -> j loop
     let mut total_pair: usize = 0;
     for (i, particle) in particles.iter().enumerate() {
-        let cell_x = ((particle.x[0] - min_x) / cell_size).floor() as isize;
-        let cell_y = ((particle.x[1] - min_y) / cell_size).floor() as isize;
-        let cell_z = ((particle.x[2] - min_z) / cell_size).floor() as isize;
-
-        // Check the 8 surrounding cells (self cell + neighboring cells)
-        for dx in -1..=1 {
-            for dy in -1..=1 {
-                for dz in -1..=1 {
-                    let neighbor_cell = (cell_x + dx, cell_y + dy, cell_z + dz);
-
-                    if let Some(neighbors) =
-                        grid.get(&(neighbor_cell.0 as usize, neighbor_cell.1 as usize, neighbor_cell.2 as usize))
-                    {
-                        for &j in neighbors {
-                            if i != j {
-                                let d = particles[i].x.metric_distance(&particles[j].x);
-
-                                if total_pair >= max_pair_n {
-                                    return Err(SimError::ExceededMaxNumber {
-                                        n: total_pair,
-                                        max_n: max_pair_n,
-                                    });
-                                }
-
-                                // If the distance is valid, add as a neighboring pair
-                                if d < smooth_length_squared.simd_sqrt() {
-                                    total_pair += 1;
-
-                                    // Store pair particles
-                                    neigh_lists[total_pair].i = i;
-                                    neigh_lists[total_pair].j = j;
-
-                                    let q = d / smooth_length;
-
-                                    let (w, dwdq) = b_spline_kernel(q);
-                                    let mut dwdr = na::Vector::from([dwdq / smooth_length; DIM]);
-
-                                    // multiply dwdr by base vector: ei = x/r
-                                    dwdr[0] *= particles[i].x[0] / d;
-                                    dwdr[1] *= particles[i].x[1] / d;
-                                    dwdr[2] *= particles[i].x[2] / d;
-
-                                    neigh_lists[total_pair].w = w;
-                                    neigh_lists[total_pair].dwdr = dwdr;
-                                }
-                            }
+        let cell = cell_location(&particle.x, &min, cell_size);
+
+        // Check the surrounding cells (self cell + neighboring cells)
+        for k in 0..NEIGHBOR_CELLS {
+            if let Some(neighbors) = grid.get(&neighbor_cell(&cell, k)) {
+                for &j in neighbors {
+                    if i != j {
+                        let d = particles[i].x.metric_distance(&particles[j].x);
+                        let support = 2.0 * particles[i].h.max(particles[j].h);
+
+                        if total_pair + 1 >= max_pair_n {
+                            return Err(SimError::ExceededMaxNumber {
+                                n: total_pair + 1,
+                                max_n: max_pair_n,
+                            });
+                        }
+
+                        // If the distance is valid, add as a neighboring pair
+                        if d < support {
+                            total_pair += 1;
+
+                            // Store pair particles
+                            neigh_lists[total_pair].i = i;
+                            neigh_lists[total_pair].j = j;
+
+                            let (w, dwdr) = pair_kernel(particles, i, j);
+                            neigh_lists[total_pair].w = w;
+                            neigh_lists[total_pair].dwdr = dwdr;
                         }
                     }
                 }
@@ -136,9 +140,14 @@ pub(crate) fn search_near_particles(
 }
 
 pub fn make_neighboring_list(particles: &mut [Particle<DIM>], neighbors: &[Neighbor<DIM>]) {
-    for (pair, neigh) in neighbors.iter().enumerate() {
-        // calculate pair numbers per one particle
-        particles[neigh.i].pair = pair;
+    // Last pair of each particle; particles without pairs get an empty range
+    let mut last = 0;
+    let mut pairs = neighbors.iter().enumerate().peekable();
+    for (i, particle) in particles.iter_mut().enumerate() {
+        while let Some((pair, _)) = pairs.next_if(|(_, neigh)| neigh.i == i) {
+            last = pair;
+        }
+        particle.pair = last;
     }
 }
 
@@ -167,3 +176,44 @@ fn write_kernel_to_csv(particles: &[Particle<DIM>], neighbors: &[Neighbor<DIM>])
     std::fs::write(&filename, &csv).with_context(|_| FailedWriteFileSnafu { path: filename });
     Ok(())
 }
+
+#[cfg(all(test, feature = "dim2"))]
+mod tests {
+    use super::*;
+    use utils::materials::Material;
+
+    /// Square lattice in the x-y plane: an interior particle with h = 1.2 dx has the 20 neighbors
+    /// within 2.4 dx, all inside the kernel support, and the stored kernels sum to one
+    #[test]
+    fn test_square_lattice() {
+        let (dx, side) = (0.01, 9_u8);
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles: Vec<Particle<DIM>> = (0..side * side)
+            .map(|index| {
+                let mut p = template.clone();
+                p.x = Vector::<DIM>::new(Real::from(index % side), Real::from(index / side)) * dx;
+                p.h = 1.2 * dx;
+                p
+            })
+            .collect();
+        let max_pairs = particles.len() * 50;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = search_near_particles(&mut particles, &mut neighbors, max_pairs, 0.012, 2.0).expect("neighbor search");
+
+        let center = usize::from(side * side / 2);
+        let pairs: Vec<&Neighbor<DIM>> = neighbors[1..k].iter().filter(|neigh| neigh.i == center).collect();
+        assert_eq!(pairs.len(), 20);
+        assert!(
+            pairs
+                .iter()
+                .all(|neigh| particles[neigh.j].x.metric_distance(&particles[center].x) < 2.4 * dx)
+        );
+        assert!(pairs.iter().all(|neigh| neigh.w > 0.0));
+
+        // Partition of unity: the self term plus the stored pair kernels sum to ~1
+        let volume = dx * dx;
+        let (w_self, _) = scaled_kernel(0.0, particles[center].h);
+        let sum = volume * (w_self + pairs.iter().map(|neigh| neigh.w).sum::<Real>());
+        assert!((sum - 1.0).abs() < 0.01, "sum V_j W_ij = {sum}");
+    }
+}
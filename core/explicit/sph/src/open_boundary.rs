@@ -0,0 +1,273 @@
+use crate::particle_pool::{push_particle, remove_particles};
+use rayon::prelude::*;
+use utils::{
+    boundary_velocity::BoundaryVelocity,
+    dimension::to_vector3,
+    error::SimError,
+    parameters::{
+        BufferZone, DIM, InflowProfile, InflowZone, ModelScale, NeighboringList as Neighbor, OpenBoundary, Particle, Real,
+    },
+};
+
+/// Inflow velocity of a particle located at `x` for the mean velocity `u`
+fn inflow_velocity(zone: &InflowZone, u: f64, x: &[f64], model_scale: &ModelScale) -> f64 {
+    match zone.profile {
+        InflowProfile::Uniform => u,
+        InflowProfile::Parabolic => {
+            let extent = [model_scale.length, model_scale.width, model_scale.height];
+            // Peak/mean ratio of 3/2 per transverse direction
+            (0..DIM).filter(|&d| d != zone.axis).fold(u, |u, d| {
+                let s = x[d].clamp(0.0, extent[d]);
+                u * 6.0 * s * (extent[d] - s) / (extent[d] * extent[d])
+            })
+        }
+    }
+}
+
+/// Tag the particles initially lying inside the buffer layers.
+pub(crate) fn init_buffer_zones(particles: &mut [Particle<DIM>], open_boundary: &OpenBoundary) {
+    particles.par_iter_mut().for_each(|p| {
+        for (z, zone) in open_boundary.inflow.iter().enumerate() {
+            let s = f64::from(p.x[zone.axis]);
+            if (zone.position - zone.thickness..zone.position).contains(&s) {
+                p.buffer = BufferZone::Inflow(z);
+                p.rho = zone.rho as Real;
+            }
+        }
+        for (z, zone) in open_boundary.outflow.iter().enumerate() {
+            let s = f64::from(p.x[zone.axis]);
+            if (zone.position..=zone.position + zone.thickness).contains(&s) {
+                p.buffer = BufferZone::Outflow(z);
+            }
+        }
+    });
+}
+
+/// Velocity function of each inflow zone
+///
+/// # Errors
+/// Velocity function cannot be loaded
+pub(crate) fn inflow_velocity_fns(open_boundary: &OpenBoundary) -> Result<Vec<BoundaryVelocity>, SimError> {
+    open_boundary
+        .inflow
+        .iter()
+        .map(|zone| BoundaryVelocity::new(&zone.velocity_fn))
+        .collect()
+}
+
+/// Advance the buffer layers after a time step.
+/// - inflow particles crossing the interface become fluid and are replaced by a new buffer particle upstream,
+/// - fluid particles crossing the outflow interface join the outflow buffer,
+/// - particles leaving the buffers are removed and the particle array is compacted.
+///
+/// `particles` is the whole particle storage; the first `n` particles are active.
+/// `inflow_fns` holds the velocity function of each inflow zone and
+/// `inflow_scalars` the passive scalar values of entering particles.
+/// Returns the new particle number.
+/// # Errors
+/// MAX Particles < N
+pub(crate) fn update_open_boundary(
+    particles: &mut [Particle<DIM>],
+    n: usize,
+    open_boundary: &OpenBoundary,
+    inflow_fns: &[BoundaryVelocity],
+    inflow_scalars: &[f64],
+    model_scale: &ModelScale,
+    time: f64,
+) -> Result<usize, SimError> {
+    let inflow_scalars: Vec<Real> = inflow_scalars.iter().map(|&value| value as Real).collect();
+    let mut removed = vec![false; n];
+    let mut spawned = Vec::new();
+
+    for (i, p) in particles.iter_mut().take(n).enumerate() {
+        match p.buffer {
+            BufferZone::Inflow(z) => {
+                let zone = &open_boundary.inflow[z];
+                let s = f64::from(p.x[zone.axis]);
+                if s >= zone.position {
+                    // Fresh particle enters the buffer upstream
+                    let mut fresh = p.clone();
+                    fresh.x[zone.axis] -= zone.thickness as Real;
+                    fresh.rho = zone.rho as Real;
+                    fresh.stress.fill(0.0);
+                    fresh.dvdt.fill(0.0);
+                    fresh.scalars.clone_from(&inflow_scalars);
+                    spawned.push(fresh);
+
+                    p.buffer = BufferZone::Interior;
+                } else if s < zone.position - zone.thickness {
+                    removed[i] = true;
+                }
+            }
+            BufferZone::Outflow(z) => {
+                let zone = &open_boundary.outflow[z];
+                if f64::from(p.x[zone.axis]) > zone.position + zone.thickness {
+                    removed[i] = true;
+                }
+            }
+            BufferZone::Interior => {
+                if let Some(z) = open_boundary
+                    .outflow
+                    .iter()
+                    .position(|zone| f64::from(p.x[zone.axis]) >= zone.position)
+                {
+                    p.buffer = BufferZone::Outflow(z);
+                }
+            }
+        }
+    }
+
+    // Compaction and insertion
+    let mut n = remove_particles(&mut particles[0..n], &removed);
+    for p in spawned {
+        n = push_particle(particles, n, p)?;
+    }
+
+    // Impose the inflow velocity
+    particles[0..n].par_iter_mut().for_each(|p| {
+        if let BufferZone::Inflow(z) = p.buffer {
+            let zone = &open_boundary.inflow[z];
+            let x = to_vector3(&p.x);
+            let u = inflow_fns[z].eval(zone.velocity, time, x.as_slice());
+            let u = inflow_velocity(zone, u, x.as_slice(), model_scale);
+            p.v.fill(0.0);
+            p.v[zone.axis] = u as Real;
+        }
+    });
+
+    Ok(n)
+}
+
+/// Non-reflecting treatment of the buffers: the buffer density (and thus the Tait pressure)
+/// is extrapolated from the interior fluid by a Shepard interpolation.
+pub(crate) fn extrapolate_buffer_pressure(particles: &mut [Particle<DIM>], neighbors: &[Neighbor<DIM>]) {
+    let n = particles.len();
+    let mut sum_rho = vec![0.0; n];
+    let mut sum_w = vec![0.0; n];
+
+    for Neighbor { i, j, w, .. } in neighbors.iter() {
+        if particles[*i].buffer != BufferZone::Interior && particles[*j].buffer == BufferZone::Interior {
+            let coef = w * particles[*j].volume;
+            sum_rho[*i] += coef * particles[*j].rho;
+            sum_w[*i] += coef;
+        }
+    }
+
+    particles.par_iter_mut().enumerate().for_each(|(i, p)| {
+        if p.buffer != BufferZone::Interior && sum_w[i] > 0.0 {
+            p.rho = sum_rho[i] / sum_w[i];
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighboring_lists::search_near_particles;
+    use utils::{
+        materials::Material,
+        parameters::{OutflowZone, VelocityFunction},
+    };
+
+    const DX: Real = 0.01;
+    const U: f64 = 1.0;
+
+    /// Row of 16 particles along x: 3 in the inflow buffer [-0.03, 0), 10 inside the channel
+    /// and 3 in the outflow buffer [0.1, 0.13], followed by spare particles
+    fn channel() -> (Vec<Particle<DIM>>, OpenBoundary) {
+        let open_boundary = OpenBoundary {
+            inflow: vec![InflowZone {
+                axis: 0,
+                position: 0.0,
+                thickness: 0.03,
+                velocity: U,
+                velocity_fn: VelocityFunction::Constant,
+                profile: InflowProfile::Uniform,
+                rho: 1000.0,
+            }],
+            outflow: vec![OutflowZone {
+                axis: 0,
+                position: 0.1,
+                thickness: 0.03,
+            }],
+        };
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles: Vec<Particle<DIM>> = (0..16_u8)
+            .map(|k| {
+                let mut p = template.clone();
+                p.x.fill(0.5 * DX);
+                p.x[0] = (Real::from(k) - 2.4) * DX;
+                p.v[0] = U as Real;
+                p.volume = DX.powi(DIM as i32);
+                p.h = 1.2 * DX;
+                p
+            })
+            .collect();
+        init_buffer_zones(&mut particles, &open_boundary);
+        particles.resize(particles.len() + 4, template);
+        (particles, open_boundary)
+    }
+
+    /// Particles crossing the inflow interface are replaced upstream and those leaving the outflow
+    /// buffer are removed: the particle number and the size of each layer stay constant
+    #[test]
+    fn test_channel() {
+        let (mut particles, open_boundary) = channel();
+        let inflow_fns = inflow_velocity_fns(&open_boundary).expect("velocity functions");
+        let model_scale = ModelScale {
+            length: 0.1,
+            width: 0.1,
+            height: 0.1,
+        };
+        let count = |particles: &[Particle<DIM>], zone: BufferZone| particles.iter().filter(|p| p.buffer == zone).count();
+        let mut n = 16;
+        assert_eq!(count(&particles[0..n], BufferZone::Inflow(0)), 3);
+        assert_eq!(count(&particles[0..n], BufferZone::Outflow(0)), 3);
+
+        // 1.5 spacings: two particles enter and two leave
+        let dt = 0.0025;
+        for step in 1..=6_u8 {
+            for p in &mut particles[0..n] {
+                p.x += (dt as Real) * p.v;
+            }
+            let time = f64::from(step) * dt;
+            n = update_open_boundary(&mut particles, n, &open_boundary, &inflow_fns, &[], &model_scale, time)
+                .expect("open boundary");
+        }
+
+        assert_eq!(n, 16);
+        assert_eq!(count(&particles[0..n], BufferZone::Inflow(0)), 3);
+        assert_eq!(count(&particles[0..n], BufferZone::Interior), 10);
+        assert_eq!(count(&particles[0..n], BufferZone::Outflow(0)), 3);
+        for p in &particles[0..n] {
+            let x = f64::from(p.x[0]);
+            match p.buffer {
+                BufferZone::Inflow(_) => {
+                    assert!((-0.03..0.0).contains(&x), "inflow particle at {x}");
+                    assert!((f64::from(p.v[0]) - U).abs() < 1.0e-6);
+                }
+                BufferZone::Interior => assert!((0.0..0.1).contains(&x), "interior particle at {x}"),
+                BufferZone::Outflow(_) => assert!((0.1..=0.13).contains(&x), "outflow particle at {x}"),
+            }
+        }
+
+        // The buffers take the density of the neighboring interior fluid
+        let rho = 1010.0;
+        for p in &mut particles[0..n] {
+            if p.buffer == BufferZone::Interior {
+                p.rho = rho;
+            }
+        }
+        let initial: Vec<Real> = particles[0..n].iter().map(|p| p.rho).collect();
+        let max_pairs = n * 100;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = search_near_particles(&mut particles[0..n], &mut neighbors, max_pairs, 0.012, 2.0).expect("neighbor search");
+        extrapolate_buffer_pressure(&mut particles[0..n], &neighbors[0..k]);
+        // Beyond the support of the interior particles the buffer density is left as is
+        for (p, &initial) in particles[0..n].iter().zip(&initial) {
+            let x = f64::from(p.x[0]);
+            let expected = if (-0.02..0.12).contains(&x) { rho } else { initial };
+            assert!((p.rho - expected).abs() < 1.0e-3, "rho = {} at {x}", p.rho);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use utils::{
+    error::SimError,
+    parameters::{DIM, Particle},
+};
+
+/// Append a particle behind the `n` active ones.
+/// Returns the new particle number.
+pub(crate) fn push_particle(particles: &mut [Particle<DIM>], n: usize, particle: Particle<DIM>) -> Result<usize, SimError> {
+    if n >= particles.len() {
+        return Err(SimError::ExceededMaxNumber {
+            n: n + 1,
+            max_n: particles.len(),
+        });
+    }
+    particles[n] = particle;
+    Ok(n + 1)
+}
+
+/// Compact the active particles by dropping the flagged ones.
+/// The order of the remaining particles is preserved. Returns the new particle number.
+pub(crate) fn remove_particles(particles: &mut [Particle<DIM>], removed: &[bool]) -> usize {
+    let mut n = 0;
+    for (i, &remove) in removed.iter().enumerate() {
+        if !remove {
+            particles.swap(n, i);
+            n += 1;
+        }
+    }
+    n
+}
@@ -0,0 +1,69 @@
+use rayon::prelude::*;
+use utils::parameters::{DIM, Particle, PorousZone, Real};
+
+/// Darcy–Forchheimer drag inside the porous zones.
+///
+/// The drag per bulk volume, f = mu / K u + rho C_F / sqrt(K) |u| u with the superficial velocity u = n v,
+/// acts on the fluid mass n rho (porosity-scaled density), so the pore velocity v decays with
+/// lambda = mu / (rho K) + n C_F |v| / sqrt(K).
+/// The drag is integrated semi-implicitly (v / (1 + lambda dt)) to stay stable for small permeabilities.
+pub(crate) fn apply_porous_drag(dt: f64, particles: &mut [Particle<DIM>], zones: &[PorousZone]) {
+    particles.par_iter_mut().filter(|p| !p.is_rigid()).for_each(|p| {
+        let Some(zone) = zones.iter().rev().find(|zone| zone.region.contains(p)) else {
+            p.porosity = 1.0;
+            return;
+        };
+        p.porosity = zone.porosity as Real;
+
+        let darcy = f64::from(p.viscosity / p.rho) / zone.permeability;
+        let forchheimer = zone.porosity * zone.forchheimer * f64::from(p.v.norm()) / zone.permeability.sqrt();
+        let lambda = darcy + forchheimer;
+
+        p.v /= lambda.mul_add(dt, 1.0) as Real;
+        let lambda = lambda as Real;
+        p.dvdt -= lambda * p.v;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{materials::Material, parameters::Region};
+
+    /// At constant velocity the drag per unit mass is the Darcy term mu / (rho K) v,
+    /// plus the Forchheimer term n C_F |v| / sqrt(K) v; particles outside the zone are not slowed down
+    #[test]
+    fn test_darcy_drag() {
+        let permeability = 1.0e-6;
+        let zone = |forchheimer| PorousZone {
+            region: Region::Box {
+                min: [0.0; 3],
+                max: [0.5, 1.0, 1.0],
+            },
+            porosity: 0.4,
+            permeability,
+            forchheimer,
+        };
+        let mut inside = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        inside.x.fill(0.25);
+        inside.v[0] = 0.01;
+        let mut outside = inside.clone();
+        outside.x[0] = 0.75;
+        let speed = f64::from(inside.v[0]);
+        let darcy = f64::from(inside.viscosity / inside.rho) / permeability;
+
+        let dt = 1.0e-9;
+        for (forchheimer, lambda) in [(0.0, darcy), (0.5, 0.4 * 0.5 * speed / permeability.sqrt() + darcy)] {
+            let mut particles = vec![inside.clone(), outside.clone()];
+            apply_porous_drag(dt, &mut particles, &[zone(forchheimer)]);
+
+            let (drag, expected) = (-f64::from(particles[0].dvdt[0]), lambda * speed);
+            assert!((drag - expected).abs() < 1.0e-4 * expected, "{drag} != {expected}");
+            assert!((particles[0].porosity - 0.4).abs() < 1.0e-6);
+            assert!((f64::from(particles[0].v[0]) - speed / lambda.mul_add(dt, 1.0)).abs() < 1.0e-6 * speed);
+
+            assert!(particles[1].dvdt.norm() == 0.0 && particles[1].v == outside.v);
+            assert!((particles[1].porosity - 1.0).abs() < Real::EPSILON);
+        }
+    }
+}
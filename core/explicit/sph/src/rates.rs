@@ -0,0 +1,255 @@
+use super::{
+    acceleration::update_acceleration,
+    artificial_viscosity::update_artificial_viscosity,
+    density::update_density_rate,
+    rotating_frame::FrameMotion,
+    solid::{add_artificial_stress, update_solid_stress},
+    sph_utils::{Tensor, Velocity, velocity_gradient, with_neighbors},
+    stress::{erode_sediment, update_granular_stress, update_stress},
+};
+use rayon::prelude::*;
+use utils::{
+    error::SimError,
+    materials::MaterialLibrary,
+    parameters::{DIM, GranularConfig, NeighboringList as Neighbor, Particle, Real, SolidConfig, Vector},
+};
+
+/// Time derivatives of the evolved fields at the current particle state
+pub(crate) trait Rates {
+    /// Continuity equation: `drhodt` from the current velocities
+    /// # Errors
+    /// Nan value occurs
+    fn continuity(&mut self, particles: &mut [Particle<DIM>]) -> Result<(), SimError>;
+
+    /// `continuity` of the `active` particles only, the others keep their `drhodt`
+    /// # Errors
+    /// Nan value occurs
+    fn active_continuity(&mut self, particles: &mut [Particle<DIM>], active: &[bool]) -> Result<(), SimError> {
+        let saved: Vec<_> = particles.iter().map(|p| p.drhodt).collect();
+        self.continuity(particles)?;
+        particles
+            .par_iter_mut()
+            .zip(saved.par_iter().zip(active.par_iter()))
+            .filter(|(_, (_, active))| !**active)
+            .for_each(|(p, (drhodt, _))| p.drhodt = *drhodt);
+        Ok(())
+    }
+
+    /// Momentum and energy equations: `dvdt` and `dedt` from the current positions, velocities and densities
+    /// # Errors
+    /// Nan value occurs
+    fn forces(&mut self, particles: &mut [Particle<DIM>], time: f64) -> Result<(), SimError>;
+
+    /// `forces` of the `active` particles only, the others keep their `dvdt` and `dedt`
+    /// # Errors
+    /// Nan value occurs
+    fn active_forces(&mut self, particles: &mut [Particle<DIM>], time: f64, active: &[bool]) -> Result<(), SimError> {
+        let saved: Vec<_> = particles.iter().map(|p| (p.dvdt, p.dedt)).collect();
+        self.forces(particles, time)?;
+        restore_inactive(particles, &saved, active);
+        Ok(())
+    }
+
+    /// All rates at `time`
+    /// # Errors
+    /// Nan value occurs
+    fn evaluate(&mut self, particles: &mut [Particle<DIM>], time: f64) -> Result<(), SimError> {
+        self.continuity(particles)?;
+        self.forces(particles, time)
+    }
+}
+
+/// Rates of the SPH model on a fixed neighbor table
+pub(crate) struct SphRates<'a> {
+    pub(crate) neighbors: &'a mut [Neighbor<DIM>],
+    pub(crate) diff_velocity: &'a mut [Velocity<DIM>],
+    pub(crate) diff_stress: &'a mut [Tensor<DIM>],
+    pub(crate) beta: f64,
+    /// Sums in a fixed order
+    pub(crate) deterministic: bool,
+    pub(crate) gravity: &'a [f64; 3],
+    pub(crate) frame_motion: Option<&'a FrameMotion>,
+    pub(crate) solids: &'a [SolidConfig],
+    pub(crate) granular: &'a [GranularConfig],
+    /// Kernel at the initial particle spacing (artificial stress of solids)
+    pub(crate) w_dp: Real,
+    /// Fluid materials of the eroded sediment
+    pub(crate) materials: &'a MaterialLibrary,
+}
+
+impl Rates for SphRates<'_> {
+    fn continuity(&mut self, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+        update_density_rate(particles, self.neighbors, self.diff_velocity, None)
+    }
+
+    /// The density rate of the neighbors of the active particles is refreshed as well
+    /// (their velocity divergence enters the viscous stress)
+    fn active_continuity(&mut self, particles: &mut [Particle<DIM>], active: &[bool]) -> Result<(), SimError> {
+        let selected = with_neighbors(particles, self.neighbors, active);
+        update_density_rate(particles, self.neighbors, self.diff_velocity, Some(&selected))
+    }
+
+    fn forces(&mut self, particles: &mut [Particle<DIM>], time: f64) -> Result<(), SimError> {
+        self.forces_of(particles, time, None)
+    }
+
+    /// The stresses are evaluated for the active particles and their neighbors, the stress divergence
+    /// and the power only for the active ones: the pair loops cover the pairs of these particles only
+    fn active_forces(&mut self, particles: &mut [Particle<DIM>], time: f64, active: &[bool]) -> Result<(), SimError> {
+        let saved: Vec<_> = particles.iter().map(|p| (p.dvdt, p.dedt)).collect();
+        self.forces_of(particles, time, Some(active))?;
+        restore_inactive(particles, &saved, active);
+        Ok(())
+    }
+}
+
+impl SphRates<'_> {
+    /// Momentum and energy equations of the `active` particles (all for `None`)
+    /// # Errors
+    /// Nan value occurs
+    fn forces_of(&mut self, particles: &mut [Particle<DIM>], time: f64, active: Option<&[bool]>) -> Result<(), SimError> {
+        // Stresses entering the stress divergence of the active particles
+        let selected = active.map(|active| with_neighbors(particles, self.neighbors, active));
+        let selected = selected.as_deref();
+        update_artificial_viscosity(particles, self.neighbors, self.beta, self.deterministic, selected);
+
+        // Stress of the current state: the deviatoric stress of solids and the dilatancy
+        // of granular materials are advanced once per step (`advance_materials`)
+        update_stress(particles, self.neighbors, self.diff_velocity, selected)?;
+        if !self.solids.is_empty() {
+            update_solid_stress(0.0, particles, self.neighbors, self.solids, selected)?;
+        }
+        if !self.granular.is_empty() {
+            update_granular_stress(0.0, particles, self.neighbors, self.granular, selected)?;
+        }
+
+        update_acceleration(particles, self.neighbors, self.diff_stress, self.gravity, active)?;
+        if let Some(frame) = self.frame_motion {
+            frame.add_frame_acceleration(particles, time);
+        }
+        if !self.solids.is_empty() {
+            add_artificial_stress(particles, self.neighbors, self.solids, self.w_dp, active);
+        }
+
+        update_power(particles, self.neighbors, active);
+        Ok(())
+    }
+
+    /// Advance the history-dependent material states over `dt`: the deviatoric stress of solids,
+    /// the dilatancy of granular materials and the erosion of sediments.
+    /// # Errors
+    /// Nan value occurs
+    pub(crate) fn advance_materials(&self, dt: f64, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+        if !self.solids.is_empty() {
+            update_solid_stress(dt, particles, self.neighbors, self.solids, None)?;
+        }
+        if !self.granular.is_empty() {
+            update_granular_stress(dt, particles, self.neighbors, self.granular, None)?;
+            erode_sediment(particles, self.neighbors, self.granular, self.materials);
+        }
+        Ok(())
+    }
+}
+
+/// Rates `saved` before the evaluation back to the inactive particles
+fn restore_inactive(particles: &mut [Particle<DIM>], saved: &[(Vector<DIM>, Real)], active: &[bool]) {
+    particles
+        .par_iter_mut()
+        .zip(saved.par_iter().zip(active.par_iter()))
+        .filter(|(_, (_, active))| !**active)
+        .for_each(|(p, ((dvdt, dedt), _))| {
+            p.dvdt = *dvdt;
+            p.dedt = *dedt;
+        });
+}
+
+/// Power of the `active` particles (all for `None`): de/dt = m v . dv/dt + V sigma : grad(v)
+fn update_power(particles: &mut [Particle<DIM>], neighbors: &[Neighbor<DIM>], active: Option<&[bool]>) {
+    let grad_v = velocity_gradient(particles, neighbors, active, |p| !p.is_rigid());
+    particles
+        .par_iter_mut()
+        .zip(grad_v.par_iter())
+        .enumerate()
+        .filter(|(i, (p, _))| !p.is_rigid() && active.is_none_or(|active| active[*i]))
+        .for_each(|(_, (p, grad_v))| {
+            let kinetic = p.rho * p.volume * p.v.dot(&p.dvdt);
+            let internal = p.volume * p.stress.component_mul(grad_v).sum();
+            p.dedt = kinetic + internal;
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighboring_lists::search_near_particles;
+    use utils::materials::Material;
+
+    /// Lattice of 6^DIM particles with a linear velocity and density field (no physical viscosity)
+    fn lattice() -> (Vec<Particle<DIM>>, Vec<Neighbor<DIM>>) {
+        let dx: Real = 0.01;
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles = Vec::new();
+        for index in 0..6_usize.pow(DIM as u32) {
+            let mut p = template.clone();
+            let mut rest = index;
+            for d in 0..DIM {
+                #[allow(clippy::cast_precision_loss)]
+                let coordinate = (rest % 6) as Real;
+                p.x[d] = coordinate * dx;
+                rest /= 6;
+            }
+            p.v = Vector::<DIM>::from_fn(|d, _| 0.1 * p.x[(d + 1) % DIM]) + 0.2 * p.x;
+            p.rho = p.rho0 * 0.01_f64.mul_add(f64::from(p.x[0] / dx), 1.0) as Real;
+            p.volume = dx.powi(DIM as i32);
+            p.h = 1.2 * dx;
+            p.viscosity = 0.0;
+            particles.push(p);
+        }
+        let max_pairs = particles.len() * 100;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = search_near_particles(&mut particles, &mut neighbors, max_pairs, 0.012, 2.0).expect("neighbor search");
+        neighbors.truncate(k);
+        (particles, neighbors)
+    }
+
+    /// The rates of the active particles from their pairs and those of their neighbors only
+    /// equal the full evaluation; the inactive particles keep their rates
+    #[test]
+    fn test_active_rates() {
+        let (mut particles, mut neighbors) = lattice();
+        let n = particles.len();
+        let mut diff_velocity: Vec<_> = (0..n).map(|_| Velocity::new()).collect();
+        let mut diff_stress: Vec<_> = (0..n).map(|_| Tensor::new()).collect();
+        let mut rates = SphRates {
+            neighbors: &mut neighbors,
+            diff_velocity: &mut diff_velocity,
+            diff_stress: &mut diff_stress,
+            beta: 0.3,
+            deterministic: false,
+            gravity: &[0.0; 3],
+            frame_motion: None,
+            solids: &[],
+            granular: &[],
+            w_dp: 0.0,
+            materials: &MaterialLibrary::default(),
+        };
+
+        let mut full = particles.clone();
+        rates.evaluate(&mut full, 0.0).expect("full rates");
+
+        let active: Vec<bool> = (0..n).map(|i| i % 7 == 3).collect();
+        rates.active_continuity(&mut particles, &active).expect("active continuity");
+        rates.active_forces(&mut particles, 0.0, &active).expect("active forces");
+
+        for (i, (p, q)) in particles.iter().zip(&full).enumerate() {
+            if active[i] {
+                assert!((p.drhodt - q.drhodt).abs() <= 1.0e-9 * q.drhodt.abs(), "drhodt of {i}");
+                assert!((p.dvdt - q.dvdt).norm() <= 1.0e-9 * q.dvdt.norm(), "dvdt of {i}");
+                assert!((p.dedt - q.dedt).abs() <= 1.0e-9 * q.dedt.abs(), "dedt of {i}");
+            } else {
+                assert_eq!(p.dvdt, Vector::<DIM>::zeros(), "dvdt of {i}");
+            }
+        }
+        assert!(full.iter().any(|q| q.dvdt.norm() > 0.0 && q.drhodt.abs() > 0.0));
+    }
+}
@@ -0,0 +1,300 @@
+use super::{
+    particle_pool::{push_particle, remove_particles},
+    sph_utils::{DIM_F, velocity_gradient},
+};
+use utils::{
+    dimension::{to_matrix3, to_vector3},
+    error::SimError,
+    parameters::{
+        AXES, BufferZone, DIM, Matrix, ModelScale, NeighboringList as Neighbor, Particle, Phase, Real, RefinementConfig,
+        Vector,
+    },
+};
+
+/// Number of daughters of a split particle (8 in 3D, 4 in 2D)
+const DAUGHTERS: usize = 1 << DIM;
+
+/// Fluid particles away from open boundaries take part in the refinement
+fn is_refinable(p: &Particle<DIM>) -> bool {
+    p.phase == Phase::Fluid && p.buffer == BufferZone::Interior
+}
+
+/// Distance to the nearest wall of the model box
+fn wall_distance(p: &Particle<DIM>, model_scale: &ModelScale) -> f64 {
+    let size = [model_scale.length, model_scale.width, model_scale.height];
+    let x = to_vector3(&p.x);
+    AXES.iter().map(|&d| x[d].min(size[d] - x[d])).fold(f64::INFINITY, f64::min)
+}
+
+/// Particles inside the refinement zone
+fn refinement_zone(
+    particles: &[Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    config: &RefinementConfig,
+    model_scale: &ModelScale,
+) -> Vec<bool> {
+    let vorticity: Option<Vec<f64>> = config.vorticity_threshold.map(|_| {
+        velocity_gradient(particles, neighbors, None, is_refinable)
+            .iter()
+            .map(|l| {
+                let l = to_matrix3(l);
+                let curl = nalgebra::Vector3::new(l[(2, 1)] - l[(1, 2)], l[(0, 2)] - l[(2, 0)], l[(1, 0)] - l[(0, 1)]);
+                curl.norm()
+            })
+            .collect()
+    });
+
+    particles
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            config.regions.iter().any(|region| region.contains(p))
+                || config
+                    .near_wall
+                    .is_some_and(|distance| wall_distance(p, model_scale) < distance)
+                || config
+                    .vorticity_threshold
+                    .zip(vorticity.as_ref())
+                    .is_some_and(|(threshold, vorticity)| vorticity[i] > threshold)
+        })
+        .collect()
+}
+
+/// Split a particle into 2^D daughters on the corners of a cube (square) of half its spacing.
+/// Mass, momentum and the intensive quantities are inherited.
+fn split(parent: &Particle<DIM>) -> Vec<Particle<DIM>> {
+    let offset = 0.25 * parent.volume.powf(DIM_F.recip());
+    (0..DAUGHTERS)
+        .map(|c| {
+            let mut daughter = parent.clone();
+            for d in 0..DIM {
+                let sign = if (c >> d) & 1 == 0 { -1.0 } else { 1.0 };
+                daughter.x[d] += sign * offset;
+            }
+            #[allow(clippy::cast_precision_loss)]
+            let count = DAUGHTERS as Real;
+            daughter.volume /= count;
+            daughter.h *= 0.5;
+            daughter.level += 1;
+            daughter
+        })
+        .collect()
+}
+
+/// Merge a group of daughters into one particle conserving mass and momentum.
+/// Intensive quantities are mass (or volume) weighted.
+fn merge(particles: &[Particle<DIM>], group: &[usize]) -> Particle<DIM> {
+    let mut merged = particles[group[0]].clone();
+
+    let mass: Real = group.iter().map(|&i| particles[i].rho * particles[i].volume).sum();
+    let volume: Real = group.iter().map(|&i| particles[i].volume).sum();
+    let mass_weighted = |value: &dyn Fn(&Particle<DIM>) -> Real| -> Real {
+        group
+            .iter()
+            .map(|&i| particles[i].rho * particles[i].volume * value(&particles[i]))
+            .sum::<Real>()
+            / mass
+    };
+
+    let mut x = Vector::<DIM>::zeros();
+    let mut v = Vector::<DIM>::zeros();
+    let mut dvdt = Vector::<DIM>::zeros();
+    let mut stress = Matrix::<DIM>::zeros();
+    let mut deviatoric = Matrix::<DIM>::zeros();
+    for &i in group {
+        let p = &particles[i];
+        let m = p.rho * p.volume;
+        x += m * p.x;
+        v += m * p.v;
+        dvdt += m * p.dvdt;
+        stress += p.volume * p.stress;
+        deviatoric += p.volume * p.deviatoric;
+    }
+    merged.x = x / mass;
+    merged.v = v / mass;
+    merged.dvdt = dvdt / mass;
+    merged.stress = stress / volume;
+    merged.deviatoric = deviatoric / volume;
+
+    merged.e = group.iter().map(|&i| particles[i].e).sum();
+    merged.temperature = mass_weighted(&|p| p.temperature);
+    for s in 0..merged.scalars.len() {
+        merged.scalars[s] = mass_weighted(&|p| p.scalars[s]);
+    }
+
+    merged.volume = volume;
+    merged.rho = mass / volume;
+    merged.h *= 2.0;
+    merged.level -= 1;
+    merged
+}
+
+/// Split the particles entering the refinement zone and merge the daughters leaving it.
+/// The neighbor table must be rebuilt afterwards. Returns the new particle number.
+///
+/// # Errors
+/// Not enough spare particles for the daughters
+pub(crate) fn update_refinement(
+    particles: &mut [Particle<DIM>],
+    n: usize,
+    neighbors: &[Neighbor<DIM>],
+    config: &RefinementConfig,
+    model_scale: &ModelScale,
+) -> Result<usize, SimError> {
+    let refine = refinement_zone(&particles[0..n], neighbors, config, model_scale);
+
+    // Merge candidates: refined neighbors at the same level outside the zone
+    let mergeable = |i: usize| is_refinable(&particles[i]) && particles[i].level > 0 && !refine[i];
+    let mut candidates: Vec<Vec<(Real, usize)>> = vec![Vec::new(); n];
+    for neigh in neighbors {
+        let (i, j) = (neigh.i, neigh.j);
+        if i != j && mergeable(i) && mergeable(j) && particles[i].level == particles[j].level {
+            candidates[i].push((particles[i].x.metric_distance(&particles[j].x), j));
+        }
+    }
+
+    // Greedy merging of the nearest groups of daughters
+    let mut taken = vec![false; n];
+    let mut removed = vec![false; n];
+    let mut merged = Vec::new();
+    for (i, candidates) in candidates.iter_mut().enumerate() {
+        if taken[i] || candidates.len() < DAUGHTERS - 1 {
+            continue;
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let group: Vec<usize> = std::iter::once(i)
+            .chain(candidates.iter().map(|&(_, j)| j).filter(|&j| !taken[j]).take(DAUGHTERS - 1))
+            .collect();
+        if group.len() == DAUGHTERS {
+            merged.push((i, merge(particles, &group)));
+            for &j in &group {
+                taken[j] = true;
+            }
+            for &j in &group[1..] {
+                removed[j] = true;
+            }
+        }
+    }
+    for (i, particle) in merged {
+        particles[i] = particle;
+    }
+
+    // Split: the first daughter replaces the parent, the others are appended
+    let mut total = n;
+    for i in 0..n {
+        if !taken[i] && refine[i] && is_refinable(&particles[i]) && particles[i].level < config.max_level {
+            let mut daughters = split(&particles[i]).into_iter();
+            if let Some(first) = daughters.next() {
+                particles[i] = first;
+            }
+            for daughter in daughters {
+                total = push_particle(particles, total, daughter)?;
+            }
+        }
+    }
+    removed.resize(total, false);
+
+    Ok(remove_particles(&mut particles[0..total], &removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighboring_lists::search_near_particles;
+    use utils::{materials::Material, parameters::Region};
+
+    const DX: Real = 0.01;
+
+    /// Lattice of 4^DIM particles with varying density and velocity, followed by spare particles
+    fn block() -> Vec<Particle<DIM>> {
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles = Vec::new();
+        for index in 0..4_usize.pow(DIM as u32) {
+            let mut p = template.clone();
+            let mut rest = index;
+            for d in 0..DIM {
+                #[allow(clippy::cast_precision_loss)]
+                let coordinate = (rest % 4) as Real + 0.5;
+                p.x[d] = coordinate * DX;
+                rest /= 4;
+            }
+            p.v = Vector::<DIM>::from_fn(|d, _| 0.1 * p.x[(d + 1) % DIM]) + 2.0 * p.x;
+            p.rho = p.rho0 * 0.01_f64.mul_add(f64::from(p.x[0] / DX), 1.0) as Real;
+            p.volume = DX.powi(DIM as i32);
+            p.h = 1.2 * DX;
+            particles.push(p);
+        }
+        particles.resize(particles.len() + DAUGHTERS, template);
+        particles
+    }
+
+    fn neighbors(particles: &mut [Particle<DIM>]) -> Vec<Neighbor<DIM>> {
+        let max_pairs = particles.len() * 100;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = search_near_particles(particles, &mut neighbors, max_pairs, 0.012, 2.0).expect("neighbor search");
+        neighbors.truncate(k);
+        neighbors
+    }
+
+    /// Total mass and momentum of the active particles
+    fn totals(particles: &[Particle<DIM>]) -> (f64, nalgebra::Vector3<f64>) {
+        particles
+            .iter()
+            .fold((0.0, nalgebra::Vector3::zeros()), |(mass, momentum), p| {
+                let m = f64::from(p.rho * p.volume);
+                (mass + m, momentum + m * to_vector3(&p.v))
+            })
+    }
+
+    /// A particle split in the refinement zone merges back once moved out of it:
+    /// the particle number returns to the original and mass and momentum are conserved
+    #[test]
+    fn test_split_merge() {
+        let mut particles = block();
+        let n0 = particles.len() - DAUGHTERS;
+        let (mass0, momentum0) = totals(&particles[0..n0]);
+        let parent = particles[0].clone();
+        let model_scale = ModelScale {
+            length: 1.0,
+            width: 1.0,
+            height: 1.0,
+        };
+
+        // Only the first particle lies in the zone
+        let zone = 0.6 * f64::from(DX);
+        let config = RefinementConfig {
+            regions: vec![Region::Box {
+                min: [0.0; 3],
+                max: [zone; 3],
+            }],
+            ..RefinementConfig::default()
+        };
+        let pairs = neighbors(&mut particles[0..n0]);
+        let n = update_refinement(&mut particles, n0, &pairs, &config, &model_scale).expect("split");
+        assert_eq!(n, n0 + DAUGHTERS - 1);
+        assert_eq!(particles[0..n].iter().filter(|p| p.level == 1).count(), DAUGHTERS);
+        let (mass, momentum) = totals(&particles[0..n]);
+        assert!((mass - mass0).abs() < 1.0e-6 * mass0);
+        assert!((momentum - momentum0).norm() < 1.0e-6 * momentum0.norm());
+
+        // Move the block out of the zone
+        for p in &mut particles[0..n] {
+            p.x[0] += 0.5;
+        }
+        let pairs = neighbors(&mut particles[0..n]);
+        let n = update_refinement(&mut particles, n, &pairs, &config, &model_scale).expect("merge");
+        assert_eq!(n, n0);
+        assert!(particles[0..n].iter().all(|p| p.level == 0));
+        let (mass, momentum) = totals(&particles[0..n]);
+        assert!((mass - mass0).abs() < 1.0e-6 * mass0);
+        assert!((momentum - momentum0).norm() < 1.0e-6 * momentum0.norm());
+
+        // The daughters merged back into the parent
+        let merged = &particles[0];
+        let mut x = parent.x;
+        x[0] += 0.5;
+        assert!(merged.x.metric_distance(&x) < 1.0e-5);
+        assert!((merged.volume - parent.volume).abs() < 1.0e-5 * parent.volume);
+        assert!((merged.h - parent.h).abs() < 1.0e-5 * parent.h);
+    }
+}
@@ -0,0 +1,326 @@
+use nalgebra as na;
+use rayon::prelude::*;
+use utils::{
+    boundary_velocity::BoundaryVelocity,
+    dimension::{from_vector3, to_vector3},
+    error::SimError,
+    parameters::{BodyMotion, DIM, Particle, Phase, RigidBody, RigidBodyConfig},
+};
+
+/// Assign the particles to the rigid bodies and compute the mass properties
+/// (mass, center of mass, inertia tensor) from the particle distribution.
+pub(crate) fn init_rigid_bodies(particles: &mut [Particle<DIM>], configs: &[RigidBodyConfig]) -> Vec<RigidBody> {
+    // The first matching body owns the particle
+    for p in particles.iter_mut() {
+        if let Some(b) = configs.iter().position(|config| config.region.contains(p)) {
+            p.phase = Phase::Rigid(b);
+        }
+    }
+
+    configs
+        .iter()
+        .enumerate()
+        .map(|(b, config)| {
+            let members = || particles.iter().filter(move |p| p.phase == Phase::Rigid(b));
+
+            let mut body = RigidBody::default();
+            for p in members() {
+                let m = config.density * f64::from(p.volume);
+                body.mass += m;
+                body.center += m * to_vector3(&p.x);
+            }
+            if body.mass <= 0.0 {
+                return body;
+            }
+            body.center /= body.mass;
+
+            let identity = na::Matrix3::identity();
+            for p in members() {
+                let m = config.density * f64::from(p.volume);
+                let r = to_vector3(&p.x) - body.center;
+                body.inertia += m * (r.dot(&r) * identity - r * r.transpose());
+            }
+            body
+        })
+        .collect()
+}
+
+/// Velocity function of each body (only used by prescribed motions)
+///
+/// # Errors
+/// Velocity function cannot be loaded
+pub(crate) fn body_velocity_fns(configs: &[RigidBodyConfig]) -> Result<Vec<BoundaryVelocity>, SimError> {
+    configs
+        .iter()
+        .map(|body| match &body.motion {
+            BodyMotion::Prescribed { velocity_fn, .. } => BoundaryVelocity::new(velocity_fn),
+            _ => Ok(BoundaryVelocity::Constant),
+        })
+        .collect()
+}
+
+/// Integrate the rigid bodies with the fluid force and torque acting on their particles,
+/// then move the body particles rigidly.
+/// `velocity_fns` holds the velocity function of each body (only used by prescribed motions).
+pub(crate) fn update_rigid_bodies(
+    dt: f64,
+    time: f64,
+    particles: &mut [Particle<DIM>],
+    bodies: &mut [RigidBody],
+    configs: &[RigidBodyConfig],
+    velocity_fns: &[BoundaryVelocity],
+    gravity: &[f64; 3],
+) {
+    let gravity = na::Vector3::from(*gravity);
+
+    // Fluid force and torque (the body force is removed from the particle acceleration)
+    for body in bodies.iter_mut() {
+        body.force = na::Vector3::zeros();
+        body.torque = na::Vector3::zeros();
+    }
+    for p in particles.iter() {
+        if let Phase::Rigid(b) = p.phase {
+            let f = f64::from(p.rho * p.volume) * (to_vector3(&p.dvdt) - gravity);
+            let r = to_vector3(&p.x) - bodies[b].center;
+            bodies[b].force += f;
+            bodies[b].torque += r.cross(&f);
+        }
+    }
+
+    // Rigid motion of each body over dt: (translation, rotation, previous center)
+    let motions: Vec<(na::Vector3<f64>, na::UnitQuaternion<f64>, na::Vector3<f64>)> = bodies
+        .iter_mut()
+        .zip(configs)
+        .zip(velocity_fns)
+        .map(|((body, config), velocity_fn)| {
+            let center = body.center;
+            match &config.motion {
+                BodyMotion::Fixed => {
+                    body.velocity = na::Vector3::zeros();
+                    body.angular_velocity = na::Vector3::zeros();
+                }
+                BodyMotion::Prescribed {
+                    velocity,
+                    angular_velocity,
+                    ..
+                } => {
+                    let scale = velocity_fn.eval(1.0, time, center.as_slice());
+                    body.velocity = scale * na::Vector3::from(*velocity);
+                    body.angular_velocity = scale * na::Vector3::from(*angular_velocity);
+                }
+                BodyMotion::Free if body.mass > 0.0 => {
+                    if config.gravity {
+                        body.force += body.mass * gravity;
+                    }
+                    body.velocity += dt * body.force / body.mass;
+
+                    // Angular momentum in the world frame: L = R I R^T w
+                    let rotation = body.orientation.to_rotation_matrix();
+                    let inertia = rotation * body.inertia * rotation.transpose();
+                    let momentum = inertia * body.angular_velocity + dt * body.torque;
+                    body.angular_velocity = inertia.try_inverse().map_or_else(na::Vector3::zeros, |inv| inv * momentum);
+                }
+                BodyMotion::Free => {}
+            }
+
+            let rotation = na::UnitQuaternion::from_scaled_axis(dt * body.angular_velocity);
+            body.center += dt * body.velocity;
+            body.orientation = rotation * body.orientation;
+            (body.center - center, rotation, center)
+        })
+        .collect();
+
+    // Move the body particles
+    particles.par_iter_mut().for_each(|p| {
+        if let Phase::Rigid(b) = p.phase {
+            let (translation, rotation, center) = &motions[b];
+            let r = rotation * (to_vector3(&p.x) - center);
+            let x = center + translation + r;
+            let v = bodies[b].velocity + bodies[b].angular_velocity.cross(&r);
+            p.x = from_vector3(&x);
+            p.v = from_vector3(&v);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{
+        materials::Material,
+        parameters::{AXES, Real, Region, VelocityFunction},
+    };
+
+    /// Half spacing and volume of the body particles
+    const A: f64 = 0.01;
+    const VOLUME: f64 = 8.0e-6;
+
+    /// Body of 2^DIM water particles at the corners of a cube of side 2A around (0.5, 0.5, 0.5)
+    fn cube(motion: BodyMotion, density: f64) -> (Vec<Particle<DIM>>, Vec<RigidBody>, RigidBodyConfig) {
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let particles: Vec<Particle<DIM>> = (0..1_usize << DIM)
+            .map(|corner| {
+                let mut p = template.clone();
+                for d in 0..DIM {
+                    let sign = if corner >> d & 1 == 0 { -1.0 } else { 1.0 };
+                    p.x[d] = f64::mul_add(sign, A, 0.5) as Real;
+                }
+                p.volume = VOLUME as Real;
+                p
+            })
+            .collect();
+        let config = RigidBodyConfig {
+            region: Region::Box {
+                min: [0.0; 3],
+                max: [1.0; 3],
+            },
+            density,
+            motion,
+            gravity: true,
+        };
+        let mut particles = particles;
+        let bodies = init_rigid_bodies(&mut particles, std::slice::from_ref(&config));
+        (particles, bodies, config)
+    }
+
+    /// Mass, center of mass and inertia tensor of the particle distribution
+    #[test]
+    fn test_mass_properties() {
+        let (particles, bodies, config) = cube(BodyMotion::Fixed, 1000.0);
+        assert!(particles.iter().all(|p| p.phase == Phase::Rigid(0)));
+
+        let body = &bodies[0];
+        #[allow(clippy::cast_precision_loss)]
+        let count = (1_usize << DIM) as f64;
+        let m = config.density * f64::from(VOLUME as Real);
+        let mass = count * m;
+        assert!((body.mass - mass).abs() < 1.0e-12);
+
+        let mut center = na::Vector3::zeros();
+        for &axis in &AXES {
+            center[axis] = 0.5;
+        }
+        assert!((body.center - center).norm() < 1.0e-6);
+
+        // Around z: every particle at x^2 + y^2 = 2 A^2
+        let a = f64::from(A as Real);
+        let inertia = mass * 2.0 * a * a;
+        assert!((body.inertia[(2, 2)] - inertia).abs() < 1.0e-3 * inertia);
+        assert!(body.inertia[(0, 1)].abs() < 1.0e-3 * inertia);
+    }
+
+    /// Block lighter than the water around it: the hydrostatic pressure gradient balances gravity on the
+    /// particles (dv/dt = g - grad p / rho = 0), the fluid force is the buoyancy rho_f V g and the block rises
+    #[test]
+    fn test_buoyancy() {
+        let density = 500.0;
+        let (mut particles, mut bodies, config) = cube(BodyMotion::Free, density);
+        let velocity_fns = body_velocity_fns(std::slice::from_ref(&config)).expect("velocity functions");
+        let gravity = [0.0, -9.81, 0.0];
+        let rho = f64::from(particles[0].rho);
+        let volume: f64 = particles.iter().map(|p| f64::from(p.volume)).sum();
+
+        let grad_p = rho * na::Vector3::from(gravity);
+        for p in &mut particles {
+            p.dvdt = from_vector3(&(na::Vector3::from(gravity) - grad_p / f64::from(p.rho)));
+        }
+        let dt = 1.0e-3;
+        update_rigid_bodies(
+            dt,
+            0.0,
+            &mut particles,
+            &mut bodies,
+            std::slice::from_ref(&config),
+            &velocity_fns,
+            &gravity,
+        );
+
+        // Archimedes force minus the weight
+        let body = &bodies[0];
+        let lift = (rho - density) * volume * 9.81;
+        assert!((body.force[1] - lift).abs() < 1.0e-4 * lift, "{} != {lift}", body.force[1]);
+        assert!(body.force[0].abs() < 1.0e-6 * lift);
+        let acceleration = lift / body.mass;
+        assert!((body.velocity[1] - acceleration * dt).abs() < 1.0e-4 * acceleration * dt);
+    }
+
+    /// Free fall under gravity: the center follows g t^2 / 2 without rotation
+    #[test]
+    fn test_free_fall() {
+        let (mut particles, mut bodies, config) = cube(BodyMotion::Free, 1000.0);
+        let velocity_fns = body_velocity_fns(std::slice::from_ref(&config)).expect("velocity functions");
+        let gravity = [0.0, -9.81, 0.0];
+        let center = bodies[0].center;
+
+        let (dt, steps) = (1.0e-3, 100);
+        for step in 0..steps {
+            // No fluid: the particle acceleration is gravity alone
+            for p in &mut particles {
+                p.dvdt = from_vector3(&na::Vector3::from(gravity));
+            }
+            let time = f64::from(step) * dt;
+            update_rigid_bodies(
+                dt,
+                time,
+                &mut particles,
+                &mut bodies,
+                std::slice::from_ref(&config),
+                &velocity_fns,
+                &gravity,
+            );
+        }
+
+        let t = f64::from(steps) * dt;
+        let fall = center[1] - bodies[0].center[1];
+        let (expected_fall, expected_velocity) = (0.5 * 9.81 * t * t, -9.81 * t);
+        assert!((fall - expected_fall).abs() < 1.0e-2 * fall, "fall = {fall}");
+        assert!((bodies[0].velocity[1] - expected_velocity).abs() < 1.0e-4 * t);
+        assert!(bodies[0].orientation.angle() < 1.0e-12);
+
+        // The particles move with the body
+        let mean_y: f64 = particles.iter().map(|p| f64::from(p.x[1])).sum::<f64>() / particles.len() as f64;
+        assert!((mean_y - bodies[0].center[1]).abs() < 1.0e-5);
+    }
+
+    /// Prescribed translation and rotation about z: orientation and particle positions after N steps
+    #[test]
+    fn test_prescribed_rotation() {
+        let omega = 2.0;
+        let velocity = [0.1, 0.0, 0.0];
+        let (mut particles, mut bodies, config) = cube(
+            BodyMotion::Prescribed {
+                velocity,
+                angular_velocity: [0.0, 0.0, omega],
+                velocity_fn: VelocityFunction::Constant,
+            },
+            1000.0,
+        );
+        let velocity_fns = body_velocity_fns(std::slice::from_ref(&config)).expect("velocity functions");
+        let center = bodies[0].center;
+        let initial: Vec<na::Vector3<f64>> = particles.iter().map(|p| to_vector3(&p.x)).collect();
+
+        let (dt, steps) = (1.0e-3, 500);
+        for step in 0..steps {
+            let time = f64::from(step) * dt;
+            update_rigid_bodies(
+                dt,
+                time,
+                &mut particles,
+                &mut bodies,
+                std::slice::from_ref(&config),
+                &velocity_fns,
+                &[0.0; 3],
+            );
+        }
+
+        let t = f64::from(steps) * dt;
+        let body = &bodies[0];
+        let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector3::z_axis(), omega * t);
+        assert!(body.orientation.angle_to(&rotation) < 1.0e-9);
+        assert!((body.center - (center + t * na::Vector3::from(velocity))).norm() < 1.0e-12);
+        for (p, x0) in particles.iter().zip(&initial) {
+            let expected = body.center + rotation * (x0 - center);
+            assert!((to_vector3(&p.x) - expected).norm() < 1.0e-5, "{:?}", p.x);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use nalgebra as na;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use utils::{
+    boundary_velocity::BoundaryVelocity,
+    dimension::{from_vector3, to_vector3},
+    error::SimError,
+    parameters::{DIM, Particle, RotatingFrame},
+};
+
+/// Time step of the central difference for the angular acceleration [s]
+const DIFF_DT: f64 = 1.0e-6;
+
+/// Minimum number of Simpson intervals per second for the frame angle
+const ANGLE_INTERVALS_PER_SECOND: f64 = 1000.0;
+
+/// Angular velocity history of a rotating frame
+pub(crate) struct FrameMotion {
+    axis: na::Unit<na::Vector3<f64>>,
+    origin: na::Vector3<f64>,
+    angular_velocity: f64,
+    function: BoundaryVelocity,
+    /// Rotation angle [rad] accumulated up to `angle_time`
+    angle: f64,
+    angle_time: f64,
+}
+
+impl FrameMotion {
+    /// Motion of `frame`
+    /// # Errors
+    /// Velocity function cannot be loaded
+    pub(crate) fn new(frame: &RotatingFrame) -> Result<Self, SimError> {
+        Ok(Self {
+            axis: na::Unit::new_normalize(na::Vector3::from(frame.axis)),
+            origin: na::Vector3::from(frame.origin),
+            angular_velocity: frame.angular_velocity,
+            function: BoundaryVelocity::new(&frame.angular_velocity_fn)?,
+            angle: 0.0,
+            angle_time: 0.0,
+        })
+    }
+
+    /// Angular velocity [rad/s] at `time`
+    fn omega(&self, time: f64) -> f64 {
+        self.function.eval(self.angular_velocity, time, self.origin.as_slice())
+    }
+
+    /// Angular acceleration [rad/s^2] at `time` (central difference)
+    fn omega_dot(&self, time: f64) -> f64 {
+        let t0 = (time - DIFF_DT).max(0.0);
+        let t1 = time + DIFF_DT;
+        (self.omega(t1) - self.omega(t0)) / (t1 - t0)
+    }
+
+    /// Angular velocity and acceleration vectors at `time`
+    fn omega_vectors(&self, time: f64) -> (na::Vector3<f64>, na::Vector3<f64>) {
+        let axis = self.axis.into_inner();
+        (self.omega(time) * axis, self.omega_dot(time) * axis)
+    }
+
+    /// Rotation angle [rad] of the frame at `time`: the accumulated angle plus the integral of omega
+    /// since `angle_time` (composite Simpson rule)
+    fn angle(&self, time: f64) -> f64 {
+        if time <= 0.0 {
+            return 0.0;
+        }
+        let span = time - self.angle_time;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let m = ((span.abs() * ANGLE_INTERVALS_PER_SECOND).ceil() as usize).max(8) * 2;
+        #[allow(clippy::cast_precision_loss)]
+        let h = span / m as f64;
+
+        let sum: f64 = (0..=m)
+            .map(|i| {
+                let weight = if i == 0 || i == m {
+                    1.0
+                } else if i % 2 == 1 {
+                    4.0
+                } else {
+                    2.0
+                };
+                #[allow(clippy::cast_precision_loss)]
+                let t = (i as f64).mul_add(h, self.angle_time);
+                weight * self.omega(t)
+            })
+            .sum();
+        (sum * h).mul_add(1.0 / 3.0, self.angle)
+    }
+
+    /// Accumulate the rotation angle up to `time`, so that later angles only integrate from there
+    pub(crate) fn advance(&mut self, time: f64) {
+        self.angle = self.angle(time);
+        self.angle_time = time.max(0.0);
+    }
+
+    /// Add the Coriolis, centrifugal and Euler accelerations to `dvdt`:
+    /// -2 Omega x v - Omega x (Omega x r) - dOmega/dt x r, with r measured from the frame origin.
+    pub(crate) fn add_frame_acceleration(&self, particles: &mut [Particle<DIM>], time: f64) {
+        let (omega, omega_dot) = self.omega_vectors(time);
+
+        particles.par_iter_mut().filter(|p| !p.is_rigid()).for_each(|p| {
+            let r = to_vector3(&p.x) - self.origin;
+            let a = fictitious_acceleration(&omega, &omega_dot, &r, &to_vector3(&p.v));
+            p.dvdt += from_vector3(&a);
+        });
+    }
+
+    /// Copy of the particles with positions, velocities and accelerations in the inertial frame
+    /// (x = R r, v = R (v_rel + Omega x r), a = R (a_rel - a_fictitious)).
+    pub(crate) fn to_inertial(&self, particles: &[Particle<DIM>], time: f64) -> Vec<Particle<DIM>> {
+        let rotation = na::UnitQuaternion::from_axis_angle(&self.axis, self.angle(time));
+        let (omega, omega_dot) = self.omega_vectors(time);
+
+        particles
+            .par_iter()
+            .map(|p| {
+                let mut p = p.clone();
+                let r = to_vector3(&p.x) - self.origin;
+                let v_rel = to_vector3(&p.v);
+                let a_rel = to_vector3(&p.dvdt);
+
+                let x = self.origin + rotation * r;
+                let v = rotation * (v_rel + omega.cross(&r));
+                let a = rotation * (a_rel - fictitious_acceleration(&omega, &omega_dot, &r, &v_rel));
+                p.x = from_vector3(&x);
+                p.v = from_vector3(&v);
+                p.dvdt = from_vector3(&a);
+                p
+            })
+            .collect()
+    }
+}
+
+/// Particles to output: transformed into the inertial frame by `frame`, or as they are
+pub(crate) fn output_view<'a>(
+    frame: Option<&FrameMotion>,
+    particles: &'a [Particle<DIM>],
+    time: f64,
+) -> Cow<'a, [Particle<DIM>]> {
+    frame.map_or(Cow::Borrowed(particles), |frame| {
+        Cow::Owned(frame.to_inertial(particles, time))
+    })
+}
+
+/// Coriolis + centrifugal + Euler acceleration of a particle at `r` moving with `v` in the rotating frame
+fn fictitious_acceleration(
+    omega: &na::Vector3<f64>,
+    omega_dot: &na::Vector3<f64>,
+    r: &na::Vector3<f64>,
+    v: &na::Vector3<f64>,
+) -> na::Vector3<f64> {
+    -2.0 * omega.cross(v) - omega.cross(&omega.cross(r)) - omega_dot.cross(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{materials::Material, parameters::VelocityFunction};
+
+    fn frame(angular_velocity_fn: VelocityFunction) -> FrameMotion {
+        FrameMotion::new(&RotatingFrame {
+            axis: [0.0, 0.0, 1.0],
+            origin: [0.1, 0.2, 0.0],
+            angular_velocity: 2.0,
+            angular_velocity_fn,
+            output_inertial: true,
+        })
+        .expect("frame motion")
+    }
+
+    /// For a constant omega the inertial view removes the fictitious accelerations again:
+    /// a particle at rest in the inertial frame is seen at rest after the transformation
+    #[test]
+    fn test_inertial_inverse() {
+        let frame = frame(VelocityFunction::Constant);
+        let time = 0.3;
+        let mut p = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        p.x = from_vector3(&na::Vector3::new(0.5, 0.4, 0.0));
+        p.v = from_vector3(&na::Vector3::new(0.3, -0.2, 0.0));
+        let physical = na::Vector3::new(0.0, -1.0, 0.0);
+        p.dvdt = from_vector3(&physical);
+
+        let mut particles = vec![p.clone()];
+        frame.add_frame_acceleration(&mut particles, time);
+        assert!((to_vector3(&particles[0].dvdt) - physical).norm() > 0.1);
+
+        let inertial = frame.to_inertial(&particles, time);
+        let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector3::z_axis(), 2.0 * time);
+        let a = to_vector3(&inertial[0].dvdt);
+        assert!((a - rotation * physical).norm() < 1.0e-5, "{a}");
+
+        let r = to_vector3(&p.x) - frame.origin;
+        let x = to_vector3(&inertial[0].x);
+        assert!((x - (frame.origin + rotation * r)).norm() < 1.0e-5, "{x}");
+    }
+
+    /// The angle accumulated over the outputs equals the integral of omega from t = 0
+    #[test]
+    fn test_incremental_angle() {
+        let mut constant = frame(VelocityFunction::Constant);
+        let mut sine = frame(VelocityFunction::Sine {
+            frequency: 1.0,
+            phase: 0.0,
+        });
+        let two_pi = 2.0 * std::f64::consts::PI;
+        for output in 1..=40_u8 {
+            let time = 0.05 * f64::from(output);
+            constant.advance(time);
+            sine.advance(time);
+            // omega = 2 and omega = 2 sin(2 pi t)
+            let (expected_constant, expected_sine) = (2.0 * time, 2.0 * (1.0 - (two_pi * time).cos()) / two_pi);
+            assert!((constant.angle - expected_constant).abs() < 1.0e-9);
+            assert!(
+                (sine.angle - expected_sine).abs() < 1.0e-9,
+                "{} != {expected_sine}",
+                sine.angle
+            );
+        }
+        // Between outputs the angle continues from the accumulated one
+        let expected = 2.0 * (1.0 - (two_pi * 2.1).cos()) / two_pi;
+        assert!((sine.angle(2.1) - expected).abs() < 1.0e-9);
+    }
+}
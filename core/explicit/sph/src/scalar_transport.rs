@@ -0,0 +1,148 @@
+use rayon::prelude::*;
+use utils::{
+    error::{SimError, check_nan_to_error},
+    parameters::{DIM, NeighboringList as Neighbor, Particle, Real, ScalarConfig},
+};
+
+/// Allocate the scalar fields and set their initial values per region.
+pub(crate) fn init_scalars(particles: &mut [Particle<DIM>], configs: &[ScalarConfig]) {
+    particles.par_iter_mut().for_each(|p| {
+        p.scalars = configs
+            .iter()
+            .map(|config| {
+                config
+                    .regions
+                    .iter()
+                    .rev()
+                    .find(|region| region.region.contains(p))
+                    .map_or(config.initial, |region| region.value) as Real
+            })
+            .collect();
+    });
+}
+
+/// Diffusion of the scalars with the SPH Laplacian (Cleary & Monaghan 1999):
+/// dC_i/dt = sum_j 2 D V_j (C_i - C_j) (x_ij . dW_ij) / (r_ij^2 + 0.01 h_ij^2)
+/// Advection is implicit in the Lagrangian particle motion.
+pub(crate) fn update_scalars(
+    dt: f64,
+    particles: &mut [Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    configs: &[ScalarConfig],
+) -> Result<(), SimError> {
+    let diffusivity: Vec<Real> = configs.iter().map(|config| config.diffusivity as Real).collect();
+    let dt = dt as Real;
+    let mut rates = vec![vec![0.0; configs.len()]; particles.len()];
+
+    for neigh in neighbors.iter() {
+        let (i, j) = (neigh.i, neigh.j);
+        let xij = particles[i].x - particles[j].x;
+        let hij = 0.5 * (particles[i].h + particles[j].h);
+        let eta2 = 0.01 * hij * hij;
+        let coef = 2.0 * particles[j].volume * xij.dot(&neigh.dwdr) / (xij.dot(&xij) + eta2);
+
+        for (s, diffusivity) in diffusivity.iter().enumerate() {
+            rates[i][s] += diffusivity * coef * (particles[i].scalars[s] - particles[j].scalars[s]);
+        }
+    }
+
+    particles
+        .par_iter_mut()
+        .zip(rates.par_iter())
+        .enumerate()
+        .try_for_each(|(i, (p, rate))| {
+            for (value, rate) in p.scalars.iter_mut().zip(rate) {
+                *value += dt * rate;
+                check_nan_to_error(i, "scalar", *value)?;
+            }
+            Ok(())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighboring_lists::scaled_kernel;
+    use utils::{
+        materials::Material,
+        parameters::{Region, ScalarRegion, Vector},
+    };
+
+    const DX: Real = 0.01;
+
+    /// Lattice of 8 x 3^(DIM - 1) particles: the scalar is 1 in the block x < 0.04 and 0 in the other one.
+    /// The pairs carry the antisymmetric kernel gradient dW/dr x_ij / r_ij.
+    fn two_blocks(config: &ScalarConfig) -> (Vec<Particle<DIM>>, Vec<Neighbor<DIM>>) {
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles = Vec::new();
+        for index in 0..8 * 3_usize.pow(DIM as u32 - 1) {
+            let mut p = template.clone();
+            let mut rest = index;
+            for d in 0..DIM {
+                let size = if d == 0 { 8 } else { 3 };
+                #[allow(clippy::cast_precision_loss)]
+                let coordinate = (rest % size) as Real + 0.5;
+                p.x[d] = coordinate * DX;
+                rest /= size;
+            }
+            p.volume = DX.powi(DIM as i32);
+            p.h = 1.2 * DX;
+            particles.push(p);
+        }
+        init_scalars(&mut particles, std::slice::from_ref(config));
+
+        let mut neighbors = Vec::new();
+        for (i, pi) in particles.iter().enumerate() {
+            for (j, pj) in particles.iter().enumerate() {
+                let d = pi.x.metric_distance(&pj.x);
+                if i != j && d < 2.0 * pi.h {
+                    let (w, dwdr) = scaled_kernel(d, pi.h);
+                    let dwdr: Vector<DIM> = dwdr * (pi.x - pj.x) / d;
+                    neighbors.push(Neighbor { i, j, w, dwdr });
+                }
+            }
+        }
+        (particles, neighbors)
+    }
+
+    /// Total scalar and the difference of the block averages
+    fn totals(particles: &[Particle<DIM>]) -> (f64, f64) {
+        let total = particles.iter().map(|p| f64::from(p.volume * p.scalars[0])).sum();
+        let (left, right): (Vec<_>, Vec<_>) = particles.iter().partition(|p| f64::from(p.x[0]) < 0.04);
+        #[allow(clippy::cast_precision_loss)]
+        let mean =
+            |block: &[&Particle<DIM>]| block.iter().map(|p| f64::from(p.scalars[0])).sum::<f64>() / block.len() as f64;
+        (total, mean(&left) - mean(&right))
+    }
+
+    /// Diffusion between two blocks conserves the total scalar and reduces the difference between them
+    #[test]
+    fn test_two_block_diffusion() {
+        let config = ScalarConfig {
+            name: "dye".to_string(),
+            diffusivity: 1.0e-3,
+            initial: 0.0,
+            regions: vec![ScalarRegion {
+                region: Region::Box {
+                    min: [0.0; 3],
+                    max: [0.04, 1.0, 1.0],
+                },
+                value: 1.0,
+            }],
+            inflow: 0.0,
+        };
+        let (mut particles, neighbors) = two_blocks(&config);
+        let (total0, jump0) = totals(&particles);
+        assert!((jump0 - 1.0).abs() < 1.0e-12);
+
+        let mut jump = jump0;
+        for _ in 0..20 {
+            update_scalars(1.0e-3, &mut particles, &neighbors, std::slice::from_ref(&config)).expect("scalars");
+            let (total, new_jump) = totals(&particles);
+            assert!((total - total0).abs() < 1.0e-5 * total0, "{total} != {total0}");
+            assert!(new_jump < jump && new_jump > 0.0, "jump = {new_jump}");
+            jump = new_jump;
+        }
+        assert!(particles.iter().all(|p| (0.0..=1.0).contains(&p.scalars[0])));
+    }
+}
@@ -0,0 +1,1215 @@
+use super::{
+    body_force::compute_body_loads,
+    free_surface::detect_free_surface,
+    hook::{HookContext, HookedRates, SimulationHook},
+    integrator::{Integrator, new_integrator},
+    neighboring_lists::{scaled_kernel, search_near_particles},
+    open_boundary::{extrapolate_buffer_pressure, inflow_velocity_fns, init_buffer_zones, update_open_boundary},
+    porous::apply_porous_drag,
+    rates::SphRates,
+    refinement::update_refinement,
+    rigid_body::{body_velocity_fns, init_rigid_bodies, update_rigid_bodies},
+    rotating_frame::{FrameMotion, output_view},
+    scalar_transport::{init_scalars, update_scalars},
+    smoothing::conservative_smoothing,
+    smoothing_length::update_variable_h,
+    solid::init_solids,
+    sph_utils::{Tensor, Velocity},
+    stress::init_granular,
+};
+use std::{sync::mpsc::RecvTimeoutError, time::Duration};
+use utils::{
+    boundary_velocity::BoundaryVelocity,
+    bs_settings::{LidVelocity, boundary_condition},
+    cfl_condition::adaptive_dt,
+    error::SimError,
+    grid_interpolation::write_grid_fields,
+    materials::MaterialLibrary,
+    parameters::{
+        CheckpointConfig, Config, DIM, LogReporterFn, MaterialRegion, NeighboringList as Neighbor, Particle, ParticleLog,
+        Real, RigidBody, RunCommand, RunControl, StopJudgeFn, TimeStepCriterion,
+    },
+    rw_checkpoint::{self, State, read_checkpoint_and_set_buffer},
+    sim_models::make_model,
+    surface_mesh::write_surface_mesh,
+    write_csv::{append_body_force_to_csv, display_result, write_body_force_header, write_scalars_to_csv},
+};
+
+/// SPH simulation advanced step by step.
+///
+/// The particles, neighbors, time and time step can be read and modified between the steps.
+/// Velocity functions, materials and the integrator are fixed at construction.
+pub struct Simulation {
+    config: CheckpointConfig,
+    log_report: Option<LogReporterFn>,
+    stop_step: Option<StopJudgeFn>,
+    run_control: Option<RunControl>,
+    paused: bool,
+
+    // State (`particles` and `neighbors` include the spare storage)
+    particles: Vec<Particle<DIM>>,
+    neighbors: Vec<Neighbor<DIM>>,
+    bodies: Vec<RigidBody>,
+    n: usize,
+    k: usize,
+    step: usize,
+    time: f64,
+    dt: f64,
+    criterion: TimeStepCriterion,
+
+    // Fixed at construction
+    lid_fn: BoundaryVelocity,
+    inflow_fns: Vec<BoundaryVelocity>,
+    body_fns: Vec<BoundaryVelocity>,
+    frame_motion: Option<FrameMotion>,
+    inflow_scalars: Vec<f64>,
+    materials: MaterialLibrary,
+    /// Kernel at the initial particle spacing (artificial stress of solids)
+    w_dp: Real,
+    max_dt: f64,
+    integrator: Box<dyn Integrator>,
+    hooks: Vec<Box<dyn SimulationHook>>,
+    /// State at the start of the step (`crash_checkpoint`)
+    last_good: SavedState,
+
+    // Gradient and div particles
+    diff_velocity: Vec<Velocity<DIM>>,
+    diff_stress: Vec<Tensor<DIM>>,
+}
+
+/// Configuration of a restart: the model and numerics stored in the checkpoint (including `dt`),
+/// with the files, run length and particle storage of the new configuration
+fn restart_config(stored: CheckpointConfig, config: &CheckpointConfig) -> CheckpointConfig {
+    CheckpointConfig {
+        restart_file: config.restart_file.clone(),
+        out_file: config.out_file.clone(),
+        max_step: config.max_step,
+        max_n: config.max_n,
+        max_near_n: config.max_near_n,
+        ..stored
+    }
+}
+
+impl Simulation {
+    /// New simulation of the model, or restarted from `restart_file`
+    /// # Errors
+    /// MAX Particles < N, unknown material, invalid velocity function, unreadable checkpoint
+    pub fn new(config: Config) -> Result<Self, SimError> {
+        let Config {
+            checkpoint_config: config,
+            log_report,
+            stop_step,
+            run_control,
+        } = config;
+
+        // A restart continues with the configuration stored in the checkpoint
+        let buf = config.restart_file.as_ref().map(read_checkpoint_and_set_buffer).transpose()?;
+        let state = match (&config.restart_file, &buf) {
+            (Some(file), Some(buf)) => Some(rw_checkpoint::load_data_from_checkpoint::<DIM, _>(file, buf)?),
+            _ => None,
+        };
+        let config = match &state {
+            Some(state) => restart_config(state.checkpoint_config.as_ref().clone(), &config),
+            None => config,
+        };
+
+        let library = MaterialLibrary::new(config.materials_file.as_deref())?;
+        let base_material = library.index_of(&config.material)?;
+        let mut fluid = Particle::new(base_material, library.get(base_material), config.temperature);
+        fluid.h = config.smooth_length as Real;
+
+        let integrator = new_integrator(config.integrator, &config.time_step);
+        let max_dt = config.time_step.max_dt.unwrap_or(config.dt);
+        let (w_dp, _) = scaled_kernel(config.dx.dx as Real, config.smooth_length as Real);
+
+        let mut simulation = Self {
+            lid_fn: BoundaryVelocity::new(&config.u_lid_fn)?,
+            inflow_fns: inflow_velocity_fns(&config.open_boundary)?,
+            body_fns: body_velocity_fns(&config.rigid_bodies)?,
+            frame_motion: config.rotating_frame.as_ref().map(FrameMotion::new).transpose()?,
+            inflow_scalars: config.scalars.iter().map(|scalar| scalar.inflow).collect(),
+            materials: library,
+            w_dp,
+            max_dt,
+            integrator,
+            hooks: Vec::new(),
+            last_good: SavedState::default(),
+            diff_velocity: Vec::new(),
+            diff_stress: Vec::new(),
+
+            particles: Vec::new(),
+            neighbors: Vec::new(),
+            bodies: Vec::new(),
+            n: 0,
+            k: 0,
+            step: 1,
+            time: 0.0,
+            dt: config.dt,
+            criterion: TimeStepCriterion::default(),
+
+            config,
+            log_report,
+            stop_step,
+            run_control,
+            paused: false,
+        };
+
+        if let Some(state) = state {
+            simulation.restore(state, fluid);
+        } else {
+            simulation.create_model(fluid)?;
+        }
+
+        // Buffers of the particle storage (a checkpoint may hold more than `max_n` particles)
+        let capacity = simulation.particles.len();
+        simulation.diff_velocity = (0..capacity).map(|_| Velocity::new()).collect();
+        simulation.diff_stress = (0..capacity).map(|_| Tensor::new()).collect();
+        Ok(simulation)
+    }
+
+    /// Simulation continued from a checkpoint with its stored configuration
+    /// # Errors
+    /// Unreadable checkpoint, see [`Simulation::new`]
+    pub fn from_checkpoint(path: &std::path::Path, log_report: Option<LogReporterFn>) -> Result<Self, SimError> {
+        let buf = read_checkpoint_and_set_buffer(path)?;
+        let state = rw_checkpoint::load_data_from_checkpoint::<DIM, _>(path, &buf)?;
+        let checkpoint_config = CheckpointConfig {
+            restart_file: Some(path.to_path_buf()),
+            ..state.checkpoint_config.into_owned()
+        };
+        Self::new(Config {
+            checkpoint_config,
+            log_report,
+            ..Default::default()
+        })
+    }
+
+    /// Load the particles, neighbors and bodies of a checkpoint.
+    fn restore(&mut self, state: State<'_, DIM>, fluid: Particle<DIM>) {
+        self.step = state.step + 1; // Start the next step.
+        self.time = state.time;
+
+        // Restore Particles and Neighbors
+        self.particles = state.particles.to_vec();
+        self.neighbors = state.neighbors.to_vec();
+        self.bodies = state.bodies.to_vec();
+        self.n = self.particles.len();
+        self.k = self.neighbors.len();
+
+        // Scalars added since the checkpoint was written
+        let scalars = &self.config.scalars;
+        if self.particles.iter().any(|p| p.scalars.len() != scalars.len()) {
+            init_scalars(&mut self.particles, scalars);
+        }
+
+        // Spare storage for particles entering through open boundaries
+        let (max_n, max_near_n) = (self.config.max_n, self.config.max_near_n);
+        self.particles.resize(max_n.max(self.n), fluid);
+        self.neighbors.resize((max_n * max_near_n).max(self.k), Neighbor::default());
+
+        // Output restore log
+        let log = format!(
+            "Restarted from checkpoint {} at step {}, time {:.3} [ms]",
+            self.config.restart_file.clone().unwrap_or_default().display(),
+            state.step,
+            self.time * 1000.0
+        );
+        send_log(self.log_report.as_ref(), ParticleLog::LogInfo(log));
+    }
+
+    /// Create the model particles and their neighbors.
+    /// # Errors
+    /// MAX Particles < N, unknown material, too many pairs, unwritable force report
+    fn create_model(&mut self, fluid: Particle<DIM>) -> Result<(), SimError> {
+        let config = &self.config;
+        self.particles = vec![fluid; config.max_n];
+        self.neighbors = (0..config.max_n * config.max_near_n).map(|_| Neighbor::default()).collect();
+
+        // --- Initialing Simulation
+        send_log(self.log_report.as_ref(), ParticleLog::LogInfo("Creating models...".into()));
+
+        // n: total particle numbers, k: total pair particles
+        let n = make_model(&config.model, &mut self.particles, &config.model_scale, &config.dx)?;
+        let particles = &mut self.particles[0..n];
+
+        // Materials of particle groups
+        assign_material_regions(particles, &config.material_regions, &self.materials)?;
+        init_buffer_zones(particles, &config.open_boundary);
+        self.bodies = init_rigid_bodies(particles, &config.rigid_bodies);
+        init_solids(particles, &config.solids);
+        init_granular(particles, &config.granular);
+        init_scalars(particles, &config.scalars);
+        self.n = n;
+
+        send_log(
+            self.log_report.as_ref(),
+            ParticleLog::LogInfo("Searching neighboring particles...".into()),
+        );
+        self.update_neighbors()?;
+
+        if let Some(log_report) = &self.log_report {
+            display_result(
+                self.config.monitor_particle,
+                log_report,
+                self.step,
+                self.time,
+                self.particles(),
+            );
+        }
+
+        // New body force time series
+        if let Some(report) = &self.config.force_report {
+            write_body_force_header(&report.out_file)?;
+        }
+        Ok(())
+    }
+
+    /// Run until `max_step` or the stop judge, then finish.
+    /// The run commands are applied before every step.
+    /// # Errors
+    /// See [`Simulation::step`]
+    pub fn run(&mut self) -> Result<(), SimError> {
+        while !self.is_finished() {
+            if !self.control_run()? {
+                break;
+            }
+            let step = self.step;
+            self.step()?;
+            if let Some(stop_step) = &self.stop_step
+                && stop_step(step + 1)
+            {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Apply the pending run commands up to a single step; while paused, wait for `Resume` or `Step`.
+    /// Returns `false` when the stop judge ends the run during the pause.
+    /// # Errors
+    /// Checkpoint of a `WriteCheckpoint` command cannot be written
+    fn control_run(&mut self) -> Result<bool, SimError> {
+        while let Some(control) = &self.run_control {
+            if self.paused
+                && let Some(stop_step) = &self.stop_step
+                && stop_step(self.step)
+            {
+                return Ok(false);
+            }
+            let timeout = if self.paused { PAUSE_POLL } else { Duration::ZERO };
+            match control.recv_timeout(timeout) {
+                Ok(command) => {
+                    if self.apply_command(command)? {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) if self.paused => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                // Nobody is left to resume the run
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.run_control = None;
+                    self.paused = false;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Apply a run command; `true` for a single step.
+    /// # Errors
+    /// Checkpoint cannot be written
+    fn apply_command(&mut self, command: RunCommand) -> Result<bool, SimError> {
+        match command {
+            RunCommand::Pause => {
+                self.paused = true;
+                let log = format!("Paused at step {}", self.step);
+                send_log(self.log_report.as_ref(), ParticleLog::LogInfo(log));
+            }
+            RunCommand::Resume => {
+                self.paused = false;
+                let log = format!("Resumed at step {}", self.step);
+                send_log(self.log_report.as_ref(), ParticleLog::LogInfo(log));
+            }
+            RunCommand::Step => return Ok(true),
+            RunCommand::SetOutStep(out_step) => self.config.out_step = out_step.max(1),
+            RunCommand::SetLidVelocity(u_lid) => self.config.u_lid = u_lid,
+            RunCommand::SetSmoothingRate(cs_rate) => self.config.cs_rate = cs_rate,
+            RunCommand::WriteCheckpoint => {
+                self.write_checkpoint()?;
+                let log = format!("Checkpoint written at step {}", self.step);
+                send_log(self.log_report.as_ref(), ParticleLog::LogInfo(log));
+            }
+        }
+        Ok(false)
+    }
+
+    /// End of a run: the `on_finish` hooks.
+    /// # Errors
+    /// Error of a hook
+    pub fn finish(&mut self) -> Result<(), SimError> {
+        self.call_hooks(|hook, context| hook.on_finish(context))
+    }
+
+    /// Register a hook called at the points of every step (in the order of registration)
+    pub fn add_hook(&mut self, hook: Box<dyn SimulationHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Call `callback` on every hook with the current state.
+    /// # Errors
+    /// Error of a hook
+    fn call_hooks(
+        &mut self,
+        callback: impl Fn(&mut dyn SimulationHook, &mut HookContext<'_>) -> Result<(), SimError>,
+    ) -> Result<(), SimError> {
+        let mut context = HookContext {
+            step: self.step,
+            time: self.time,
+            dt: self.dt,
+            particles: &mut self.particles[0..self.n],
+            neighbors: &self.neighbors[0..self.k],
+            bodies: &mut self.bodies,
+            config: &self.config,
+            active: None,
+        };
+        self.hooks
+            .iter_mut()
+            .try_for_each(|hook| callback(hook.as_mut(), &mut context))
+    }
+
+    /// Run until the time reaches `time` [s] or `max_step`.
+    /// # Errors
+    /// See [`Simulation::step`]
+    pub fn run_until(&mut self, time: f64) -> Result<(), SimError> {
+        while self.time < time && !self.is_finished() {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Advance one time step, with the output at every `out_step`.
+    /// # Errors
+    /// MAX Particles < N, Nan value occurs (with the crash checkpoints, see `crash_checkpoint`)
+    pub fn step(&mut self) -> Result<(), SimError> {
+        if self.config.crash_checkpoint {
+            self.save_last_good();
+        }
+        let result = self.try_step().map_err(|error| self.diagnose(error));
+        if let Err(error) = &result {
+            let _ = self.call_hooks(|hook, context| {
+                hook.on_error(error, context);
+                Ok(())
+            });
+        }
+        result
+    }
+
+    /// One time step without the crash checkpoints
+    /// # Errors
+    /// See [`Simulation::step`]
+    fn try_step(&mut self) -> Result<(), SimError> {
+        (self.dt, self.criterion) = adaptive_dt(self.dt, self.max_dt, self.particles(), &self.config.time_step)?;
+
+        self.call_hooks(|hook, context| hook.before_boundary_conditions(context))?;
+        self.apply_boundary_conditions();
+        self.call_hooks(|hook, context| hook.after_boundary_conditions(context))?;
+        self.advance()?;
+        self.call_hooks(|hook, context| hook.after_integration(context))?;
+
+        self.update_particle_number()?;
+        if self.step.is_multiple_of(self.config.out_step) {
+            self.output()?;
+        }
+
+        // Increment
+        self.time += self.dt;
+        self.step += 1;
+        Ok(())
+    }
+
+    /// Keep the state at the start of the step
+    fn save_last_good(&mut self) {
+        let saved = &mut self.last_good;
+        saved.particles.clear();
+        saved.particles.extend_from_slice(&self.particles[0..self.n]);
+        saved.neighbors.clear();
+        saved.neighbors.extend_from_slice(&self.neighbors[0..self.k]);
+        saved.bodies.clone_from(&self.bodies);
+        saved.step = self.step;
+        saved.time = self.time;
+    }
+
+    /// Failure of a step with the state around a Nan, after writing the crash checkpoints
+    fn diagnose(&self, error: SimError) -> SimError {
+        if !matches!(
+            error,
+            SimError::CannotContinueSimNan { .. } | SimError::TimeStepTooSmall { .. }
+        ) {
+            return error;
+        }
+        if self.config.crash_checkpoint {
+            self.write_crash_checkpoints();
+        }
+
+        let SimError::CannotContinueSimNan { i, field } = error else {
+            return error;
+        };
+        SimError::SimulationBlowUp {
+            step: self.step,
+            time: self.time,
+            i,
+            field,
+            neighbors: neighbor_summary(self.particles(), self.neighbors(), i),
+        }
+    }
+
+    /// Write the last good state (`<out_file>_last_good`), from which the run can be restarted,
+    /// and the failed state (`<out_file>_crash`). Failures to write are only logged.
+    fn write_crash_checkpoints(&self) {
+        let config = &self.config;
+        let saved = &self.last_good;
+        let good_file = crash_file(&config.out_file, "last_good");
+        let crash_file = crash_file(&config.out_file, "crash");
+
+        // The state at the start of a step is the one after the previous step
+        let written = rw_checkpoint::write_sim_checkpoint(
+            &good_file,
+            config,
+            &saved.particles,
+            &saved.neighbors,
+            &saved.bodies,
+            saved.step.saturating_sub(1),
+            saved.time,
+        )
+        .and_then(|()| {
+            rw_checkpoint::write_sim_checkpoint(
+                &crash_file,
+                config,
+                self.particles(),
+                self.neighbors(),
+                &self.bodies,
+                self.step,
+                self.time,
+            )
+        });
+
+        let log = match written {
+            Ok(()) => format!(
+                "Crash checkpoints written: {} (last good state), {}",
+                good_file.display(),
+                crash_file.display()
+            ),
+            Err(error) => format!("Failed to write the crash checkpoints: {error}"),
+        };
+        send_log(self.log_report.as_ref(), ParticleLog::LogInfo(log));
+    }
+
+    /// Wall and lid velocities of the boundary particles
+    fn apply_boundary_conditions(&mut self) {
+        let config = &self.config;
+        boundary_condition(
+            &mut self.particles[0..self.n],
+            config.bc_pattern,
+            LidVelocity {
+                u_lid: config.u_lid,
+                function: &self.lid_fn,
+                time: self.time,
+            },
+            config.model_scale.clone(),
+            config.dx.clone(),
+            config.smooth_length,
+        );
+    }
+
+    /// Time integration of the particles and bodies.
+    /// # Errors
+    /// Nan value occurs, error of a hook
+    fn advance(&mut self) -> Result<(), SimError> {
+        let config = &self.config;
+        let (n, dt, time) = (self.n, self.dt, self.time);
+        let particles = &mut self.particles[0..n];
+
+        // Smoothing length following the density: neighbor-table rebuild with the new supports
+        if let Some(variable_h) = &config.variable_h {
+            self.k = update_variable_h(
+                particles,
+                &mut self.neighbors,
+                config.max_n * config.max_near_n,
+                config.smooth_length,
+                config.cell_scale,
+                variable_h,
+            )?;
+        }
+        if let Some(free_surface) = &config.free_surface {
+            detect_free_surface(particles, &self.neighbors[0..self.k], free_surface);
+        }
+
+        // Time integration of x, v, rho and e
+        let mut rates = HookedRates {
+            rates: SphRates {
+                neighbors: &mut self.neighbors[0..self.k],
+                diff_velocity: &mut self.diff_velocity[0..n],
+                diff_stress: &mut self.diff_stress[0..n],
+                beta: config.beta,
+                deterministic: config.deterministic,
+                gravity: &config.gravity,
+                frame_motion: self.frame_motion.as_ref(),
+                solids: &config.solids,
+                granular: &config.granular,
+                w_dp: self.w_dp,
+                materials: &self.materials,
+            },
+            hooks: &mut self.hooks,
+            bodies: &mut self.bodies,
+            config,
+            step: self.step,
+            dt,
+        };
+        self.integrator.step(dt, time, particles, &mut rates)?;
+        rates.rates.advance_materials(dt, particles)?;
+
+        if !config.porous_zones.is_empty() {
+            apply_porous_drag(dt, particles, &config.porous_zones);
+        }
+
+        // Rigid bodies: fluid force/torque -> body motion
+        if !self.bodies.is_empty() {
+            update_rigid_bodies(
+                dt,
+                time,
+                particles,
+                &mut self.bodies,
+                &config.rigid_bodies,
+                &self.body_fns,
+                &config.gravity,
+            );
+        }
+
+        let neighbors = &self.neighbors[0..self.k];
+        conservative_smoothing(particles, neighbors, config.cs_rate);
+
+        // Passive scalars
+        if !config.scalars.is_empty() {
+            update_scalars(dt, particles, neighbors, &config.scalars)?;
+        }
+        Ok(())
+    }
+
+    /// Open boundaries and adaptive refinement: particle insertion/deletion and neighbor-table rebuild.
+    /// # Errors
+    /// MAX Particles < N, too many pairs
+    fn update_particle_number(&mut self) -> Result<(), SimError> {
+        if !self.config.open_boundary.is_empty() {
+            let new_n = update_open_boundary(
+                &mut self.particles,
+                self.n,
+                &self.config.open_boundary,
+                &self.inflow_fns,
+                &self.inflow_scalars,
+                &self.config.model_scale,
+                self.time,
+            )?;
+            if new_n != self.n {
+                self.n = new_n;
+                self.update_neighbors()?;
+            }
+            extrapolate_buffer_pressure(&mut self.particles[0..self.n], &self.neighbors[0..self.k]);
+        }
+
+        if let Some(refinement) = &self.config.refinement
+            && self.step.is_multiple_of(refinement.interval)
+        {
+            self.n = update_refinement(
+                &mut self.particles,
+                self.n,
+                &self.neighbors[0..self.k],
+                refinement,
+                &self.config.model_scale,
+            )?;
+            self.update_neighbors()?;
+        }
+        Ok(())
+    }
+
+    /// Log report, field outputs, force report and checkpoint.
+    /// # Errors
+    /// Output file cannot be written, error of a hook
+    fn output(&mut self) -> Result<(), SimError> {
+        self.call_hooks(|hook, context| hook.on_output(context))?;
+        if let Some(frame) = &mut self.frame_motion {
+            frame.advance(self.time);
+        }
+
+        let config = &self.config;
+        let (step, time, dt) = (self.step, self.time, self.dt);
+
+        let output_frame = self
+            .frame_motion
+            .as_ref()
+            .filter(|_| config.rotating_frame.as_ref().is_some_and(|frame| frame.output_inertial));
+        let output = output_view(output_frame, self.particles(), time);
+
+        if let Some(log_report) = &self.log_report {
+            display_result(config.monitor_particle, log_report, step, time, &output);
+            log_report(ParticleLog::TimeStep {
+                step,
+                time,
+                dt,
+                criterion: self.criterion,
+            });
+        }
+        if !config.scalars.is_empty() {
+            write_scalars_to_csv(step, &output, &config.scalars)?;
+        }
+        if let Some(mesh) = &config.surface_mesh {
+            write_surface_mesh(step, &output, mesh)?;
+        }
+        if let Some(grid) = &config.grid_output {
+            write_grid_fields(step, &output, grid)?;
+        }
+        if let Some(report) = &config.force_report {
+            let loads = compute_body_loads(self.particles(), self.neighbors(), report);
+            append_body_force_to_csv(&report.out_file, step, time, &loads)?;
+            send_log(self.log_report.as_ref(), ParticleLog::BodyForce { step, time, loads });
+        }
+        self.write_checkpoint()
+    }
+
+    /// Write the checkpoint of the current state to `out_file`.
+    /// # Errors
+    /// Checkpoint cannot be written
+    pub fn write_checkpoint(&self) -> Result<(), SimError> {
+        rw_checkpoint::write_sim_checkpoint(
+            &self.config.out_file,
+            &self.config,
+            self.particles(),
+            self.neighbors(),
+            &self.bodies,
+            self.step,
+            self.time,
+        )
+    }
+
+    /// Rebuild the neighbor table, e.g. after moving particles.
+    /// # Errors
+    /// Too many pairs
+    pub fn update_neighbors(&mut self) -> Result<(), SimError> {
+        let config = &self.config;
+        self.k = search_near_particles(
+            &mut self.particles[0..self.n],
+            &mut self.neighbors,
+            config.max_n * config.max_near_n,
+            config.smooth_length,
+            config.cell_scale,
+        )?;
+        Ok(())
+    }
+
+    /// `max_step` is exceeded
+    pub const fn is_finished(&self) -> bool {
+        self.step > self.config.max_step
+    }
+
+    pub const fn config(&self) -> &CheckpointConfig {
+        &self.config
+    }
+
+    /// Parameters read every step (`u_lid`, `cs_rate`, `out_step`, `max_step`, ...)
+    pub const fn config_mut(&mut self) -> &mut CheckpointConfig {
+        &mut self.config
+    }
+
+    pub fn particles(&self) -> &[Particle<DIM>] {
+        &self.particles[0..self.n]
+    }
+
+    /// Particles to modify between the steps (see [`Simulation::update_neighbors`] after moving them)
+    pub fn particles_mut(&mut self) -> &mut [Particle<DIM>] {
+        &mut self.particles[0..self.n]
+    }
+
+    pub fn neighbors(&self) -> &[Neighbor<DIM>] {
+        &self.neighbors[0..self.k]
+    }
+
+    pub fn bodies(&self) -> &[RigidBody] {
+        &self.bodies
+    }
+
+    /// Next step number
+    pub const fn current_step(&self) -> usize {
+        self.step
+    }
+
+    /// Time [s]
+    pub const fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub const fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Time step [s] of the last step (initial `dt` before the first one)
+    pub const fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Time step [s] the adaptive control of the next step starts from
+    pub const fn set_dt(&mut self, dt: f64) {
+        self.dt = dt;
+    }
+
+    /// Criterion limiting the time step of the last step
+    pub const fn criterion(&self) -> TimeStepCriterion {
+        self.criterion
+    }
+
+    /// Paused by a run command
+    pub const fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+/// State saved at the start of a step
+#[derive(Default)]
+struct SavedState {
+    particles: Vec<Particle<DIM>>,
+    neighbors: Vec<Neighbor<DIM>>,
+    bodies: Vec<RigidBody>,
+    step: usize,
+    time: f64,
+}
+
+/// `out_file` with `suffix` appended to the file stem
+fn crash_file(out_file: &std::path::Path, suffix: &str) -> std::path::PathBuf {
+    let stem = out_file
+        .file_stem()
+        .map_or_else(Default::default, |stem| stem.to_string_lossy());
+    let mut name = format!("{stem}_{suffix}");
+    if let Some(extension) = out_file.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    out_file.with_file_name(name)
+}
+
+/// Neighbors of particle `i`: number, nearest distance, fastest speed and non-finite states
+fn neighbor_summary(particles: &[Particle<DIM>], neighbors: &[Neighbor<DIM>], i: usize) -> String {
+    let Some(p) = particles.get(i) else {
+        return format!("particle {i} out of {} particles", particles.len());
+    };
+    let pairs: Vec<&Particle<DIM>> = neighbors
+        .iter()
+        .filter_map(|neigh| match (neigh.i == i, neigh.j == i) {
+            (true, _) => particles.get(neigh.j),
+            (_, true) => particles.get(neigh.i),
+            _ => None,
+        })
+        .collect();
+    let nearest = pairs.iter().map(|q| (q.x - p.x).norm()).fold(Real::INFINITY, Real::min);
+    let fastest = pairs.iter().map(|q| q.v.norm()).fold(0.0, Real::max);
+    let non_finite = pairs.iter().filter(|q| !is_finite(q)).count();
+    format!(
+        "rho {:.3e} [kg/m^3], h {:.3e} [m], {} neighbors, nearest {nearest:.3e} [m], fastest {fastest:.3e} [m/s], {non_finite} non-finite",
+        p.rho,
+        p.h,
+        pairs.len()
+    )
+}
+
+/// Position, velocity and density are finite
+fn is_finite(p: &Particle<DIM>) -> bool {
+    p.rho.is_finite() && p.x.iter().chain(p.v.iter()).all(|value| value.is_finite())
+}
+
+/// Interval of checking the stop judge while paused
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// Send a log record to the reporter, if any
+fn send_log(log_report: Option<&LogReporterFn>, log: ParticleLog) {
+    if let Some(log_report) = log_report {
+        log_report(log);
+    }
+}
+
+/// Material of the particles inside the material regions (the last matching region wins)
+/// # Errors
+/// Unknown material name
+fn assign_material_regions(
+    particles: &mut [Particle<DIM>],
+    material_regions: &[MaterialRegion],
+    library: &MaterialLibrary,
+) -> Result<(), SimError> {
+    let region_materials = material_regions
+        .iter()
+        .map(|region| library.index_of(&region.material))
+        .collect::<Result<Vec<_>, _>>()?;
+    for p in particles {
+        if let Some(r) = material_regions.iter().rposition(|region| region.region.contains(p)) {
+            let id = region_materials[r];
+            p.set_material(id, library.get(id));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body_force::compute_body_loads;
+    use utils::parameters::{AIRFOIL_TAG, AXES, ForceReportConfig, ModelKind, ModelScale, Region, Resolution};
+
+    fn small_box() -> CheckpointConfig {
+        CheckpointConfig {
+            max_n: 1000,
+            model_scale: ModelScale {
+                length: 0.1,
+                width: 0.1,
+                height: 0.1,
+            },
+            dx: Resolution {
+                dx: 0.02,
+                dy: 0.02,
+                dz: 0.02,
+            },
+            smooth_length: 0.024,
+            max_step: 5,
+            out_step: 100,
+            ..Default::default()
+        }
+    }
+
+    /// Airfoil model files of `name`: a tagged block of 2^DIM points in a lattice of 8^DIM air points
+    fn block_model(name: &str) -> ModelKind {
+        let dir = std::env::temp_dir();
+        let airfoil_file = dir.join(format!("{name}_airfoil.csv"));
+        let air_space_file = dir.join(format!("{name}_air_space.csv"));
+        let mut airfoil = String::from("x,y,z\n");
+        let mut air_space = airfoil.clone();
+        for index in 0..8_u32.pow(DIM as u32) {
+            let mut x = [0.0; 3];
+            let mut in_body = true;
+            let mut rest = index;
+            for &axis in &AXES {
+                x[axis] = f64::from(rest % 8) * 0.01;
+                in_body &= (3..5).contains(&(rest % 8));
+                rest /= 8;
+            }
+            let line = format!("{},{},{}\n", x[0], x[1], x[2]);
+            if in_body {
+                airfoil.push_str(&line);
+            } else {
+                air_space.push_str(&line);
+            }
+        }
+        std::fs::write(&airfoil_file, airfoil).expect("airfoil file");
+        std::fs::write(&air_space_file, air_space).expect("air space file");
+        ModelKind::Airfoil {
+            airfoil_file,
+            air_space_file,
+            coordinates_file: Some(dir.join(format!("{name}_coordinates.csv"))),
+        }
+    }
+
+    /// The airfoil model tags its body, whose loads in a pressure gradient are nonzero
+    #[test]
+    fn test_airfoil_loads() {
+        let report = ForceReportConfig {
+            region: Region::Tag(AIRFOIL_TAG),
+            out_file: std::env::temp_dir().join("test_airfoil_loads.csv"),
+            ..Default::default()
+        };
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                model: block_model("test_airfoil_loads"),
+                model_scale: ModelScale {
+                    length: 0.08,
+                    width: 0.08,
+                    height: 0.08,
+                },
+                smooth_length: 0.012,
+                u_lid: 0.0,
+                force_report: Some(report.clone()),
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("new simulation");
+        let tagged = simulation.particles().iter().filter(|p| p.tag == AIRFOIL_TAG).count();
+        assert_eq!(tagged, 1 << DIM);
+        let coordinates = std::fs::read_to_string(std::env::temp_dir().join("test_airfoil_loads_coordinates.csv"))
+            .expect("coordinates file");
+        assert_eq!(coordinates.lines().count(), simulation.particles().len() + 1);
+
+        // Density (pressure) increasing along x
+        for p in simulation.particles_mut() {
+            p.rho = p.rho0 * (1.0 + p.x[0]);
+        }
+        simulation.step().expect("step");
+
+        let loads = compute_body_loads(simulation.particles(), simulation.neighbors(), &report);
+        assert!(loads.force().norm() > 0.0, "{loads:?}");
+        assert!(loads.cd.abs() > 0.0 && loads.cd.is_finite(), "{loads:?}");
+    }
+
+    /// Step-by-step run of a small box with a modification between the steps
+    #[test]
+    fn test_step_api() {
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: small_box(),
+            ..Default::default()
+        })
+        .expect("new simulation");
+        assert!(!simulation.particles().is_empty());
+        assert!(!simulation.neighbors().is_empty());
+
+        simulation.step().expect("first step");
+        assert_eq!(simulation.current_step(), 2);
+        let time = simulation.time();
+        assert!(time > 0.0);
+
+        simulation.particles_mut()[0].v[0] = 0.1;
+        simulation.run_until(1.0).expect("run");
+        assert!(simulation.is_finished());
+        assert!(simulation.time() > time);
+        assert!(simulation.particles().iter().all(|p| p.x.iter().all(|x| x.is_finite())));
+
+        // No copy of the state without the crash checkpoints
+        assert!(simulation.last_good.particles.is_empty());
+    }
+
+    /// Paused run advanced by single steps, with parameters changed mid-run
+    #[test]
+    fn test_run_control() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: small_box(),
+            stop_step: Some(Box::new(|step| step >= 3)),
+            run_control: Some(receiver),
+            ..Default::default()
+        })
+        .expect("new simulation");
+
+        for command in [
+            RunCommand::SetOutStep(50),
+            RunCommand::SetLidVelocity(0.5),
+            RunCommand::SetSmoothingRate(0.1),
+            RunCommand::Pause,
+            RunCommand::Step,
+            RunCommand::Step,
+        ] {
+            sender.send(command).expect("send command");
+        }
+        simulation.run().expect("run");
+
+        assert!(simulation.is_paused());
+        assert_eq!(simulation.current_step(), 3);
+        let config = simulation.config();
+        assert_eq!(config.out_step, 50);
+        assert!((config.u_lid - 0.5).abs() < f64::EPSILON);
+        assert!((config.cs_rate - 0.1).abs() < f64::EPSILON);
+    }
+
+    /// Checkpoint after a few steps of the box in a pool of `threads`
+    fn checkpoint_with_threads(threads: usize, out_file: &std::path::Path) -> Vec<u8> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("thread pool");
+        pool.install(|| {
+            let mut simulation = Simulation::new(Config {
+                checkpoint_config: CheckpointConfig {
+                    deterministic: true,
+                    max_step: 10,
+                    out_file: out_file.to_path_buf(),
+                    ..small_box()
+                },
+                ..Default::default()
+            })
+            .expect("new simulation");
+            simulation.run().expect("run");
+            simulation.write_checkpoint().expect("checkpoint");
+        });
+        std::fs::read(out_file).expect("read checkpoint")
+    }
+
+    /// Deterministic mode gives the same checkpoint for any number of threads
+    #[test]
+    fn test_deterministic_threads() {
+        let out_file = std::env::temp_dir().join("test_deterministic_threads.bin");
+        let single = checkpoint_with_threads(1, &out_file);
+        for threads in [2, 4, 7] {
+            assert!(checkpoint_with_threads(threads, &out_file) == single, "{threads} threads");
+        }
+    }
+
+    /// A checkpoint of more than `max_n` particles is continued with buffers of its size
+    #[test]
+    fn test_restart_above_max_n() {
+        let out_file = std::env::temp_dir().join("test_restart_above_max_n.bin");
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                out_file: out_file.clone(),
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("new simulation");
+        simulation.step().expect("step");
+        simulation.write_checkpoint().expect("checkpoint");
+        let n = simulation.particles().len();
+
+        let mut restarted = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                max_n: n / 2,
+                restart_file: Some(out_file),
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("restart");
+        assert_eq!(restarted.particles().len(), n);
+        restarted.step().expect("step after restart");
+    }
+
+    /// A restart continues with the model and numerics of the checkpoint and the run length of the new configuration
+    #[test]
+    fn test_restart_config() {
+        let out_file = std::env::temp_dir().join("test_restart_config.bin");
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                out_file: out_file.clone(),
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("new simulation");
+        simulation.step().expect("step");
+        simulation.write_checkpoint().expect("checkpoint");
+        let stored = simulation.config().clone();
+
+        let mut restarted = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                dt: 2.0 * stored.dt,
+                smooth_length: 1.5 * stored.smooth_length,
+                max_step: stored.max_step + 5,
+                restart_file: Some(out_file),
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("restart");
+        assert!((restarted.dt() - stored.dt).abs() <= f64::EPSILON * stored.dt);
+        assert!((restarted.config().smooth_length - stored.smooth_length).abs() <= f64::EPSILON);
+        assert_eq!(restarted.config().max_step, stored.max_step + 5);
+        restarted.step().expect("step after restart");
+    }
+
+    /// Forcing blowing up a particle
+    struct BlowUp;
+
+    impl SimulationHook for BlowUp {
+        fn after_forces(&mut self, context: &mut HookContext<'_>) -> Result<(), SimError> {
+            if context.step == 2 {
+                context.particles[7].dvdt[0] = Real::NAN;
+            }
+            Ok(())
+        }
+    }
+
+    /// A Nan is reported with its particle and step, and the run restarts from the last good state
+    #[test]
+    fn test_crash_checkpoint() {
+        let out_file = std::env::temp_dir().join("test_crash_checkpoint.bin");
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: CheckpointConfig {
+                out_file: out_file.clone(),
+                crash_checkpoint: true,
+                ..small_box()
+            },
+            ..Default::default()
+        })
+        .expect("new simulation");
+        simulation.add_hook(Box::new(BlowUp));
+
+        simulation.step().expect("first step");
+        let result = simulation.step();
+        assert!(
+            matches!(result, Err(SimError::SimulationBlowUp { step: 2, i: 7, .. })),
+            "{result:?}"
+        );
+
+        let good_file = crash_file(&out_file, "last_good");
+        assert!(crash_file(&out_file, "crash").exists());
+        let restarted = Simulation::from_checkpoint(&good_file, None).expect("restart");
+        assert_eq!(restarted.current_step(), 2);
+        assert!(restarted.particles().iter().all(is_finite));
+    }
+
+    /// Calls of the hook points, in order
+    struct Recorder(std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+    impl Recorder {
+        fn record(&self, point: &'static str) {
+            if let Ok(mut calls) = self.0.lock() {
+                calls.push(point);
+            }
+        }
+    }
+
+    impl SimulationHook for Recorder {
+        fn before_boundary_conditions(&mut self, _context: &mut HookContext<'_>) -> Result<(), SimError> {
+            self.record("before_bc");
+            Ok(())
+        }
+
+        fn after_forces(&mut self, context: &mut HookContext<'_>) -> Result<(), SimError> {
+            // Custom forcing
+            for p in context.particles.iter_mut() {
+                p.dvdt[0] += 1.0;
+            }
+            self.record("forces");
+            Ok(())
+        }
+
+        fn after_integration(&mut self, context: &mut HookContext<'_>) -> Result<(), SimError> {
+            self.record("integration");
+            if context.step == 2 {
+                return Err(SimError::HookFailed { message: "stop".into() });
+            }
+            Ok(())
+        }
+
+        fn on_error(&mut self, _error: &SimError, _context: &mut HookContext<'_>) {
+            self.record("error");
+        }
+    }
+
+    /// Hooks are called in the order of the step and their errors stop the simulation
+    #[test]
+    fn test_hooks() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut simulation = Simulation::new(Config {
+            checkpoint_config: small_box(),
+            ..Default::default()
+        })
+        .expect("new simulation");
+        simulation.add_hook(Box::new(Recorder(std::sync::Arc::clone(&calls))));
+
+        simulation.step().expect("first step");
+        assert!(simulation.step().is_err());
+        let calls = calls.lock().map(|calls| calls.clone()).unwrap_or_default();
+        assert_eq!(
+            calls,
+            [
+                "before_bc",
+                "forces",
+                "integration",
+                "before_bc",
+                "forces",
+                "integration",
+                "error"
+            ]
+        );
+    }
+}
@@ -1,24 +1,24 @@
-use nalgebra as na;
 use rayon::prelude::*;
-use utils::parameters::{DIM, NeighboringList as Neighbor, Particle};
+use utils::parameters::{DIM, Matrix, NeighboringList as Neighbor, Particle, Real, Vector};
 
 struct CsValue {
     /// SPH Velocity [m/s]
-    pub velocity: na::Vector3<f64>,
+    pub velocity: Vector<DIM>,
     /// SPH Cauthy stress [Pa]
-    pub stress: na::Matrix3<f64>,
+    pub stress: Matrix<DIM>,
 }
 
 impl CsValue {
     pub fn new() -> Self {
         Self {
-            velocity: na::Vector3::zeros(),
-            stress: na::Matrix3::zeros(),
+            velocity: Vector::<DIM>::zeros(),
+            stress: Matrix::<DIM>::zeros(),
         }
     }
 }
 
 pub(crate) fn conservative_smoothing(particles: &mut [Particle<DIM>], neighbors: &[Neighbor<DIM>], cs_rate: f64) {
+    let cs_rate = cs_rate as Real;
     // initialize coefficients
     let mut coef = vec![0.0; particles.len()];
     let mut cs_value: Vec<CsValue> = (0..particles.len()).map(|_| CsValue::new()).collect();
@@ -30,11 +30,11 @@ pub(crate) fn conservative_smoothing(particles: &mut [Particle<DIM>], neighbors:
         coef[*i] += coef_i;
         coef[*j] += coef_j;
 
-        let vi = na::Vector3::from(particles[*i].v);
-        let vj = na::Vector3::from(particles[*j].v);
+        let vi = particles[*i].v;
+        let vj = particles[*j].v;
 
-        let stress_i = na::Matrix3::from(particles[*i].stress);
-        let stress_j = na::Matrix3::from(particles[*j].stress);
+        let stress_i = particles[*i].stress;
+        let stress_j = particles[*j].stress;
 
         cs_value[*i].velocity += coef_i * vi;
         cs_value[*j].velocity += coef_j * vj;
@@ -0,0 +1,182 @@
+use super::{
+    neighboring_lists::{kernel_dh, pair_kernel, search_near_particles},
+    sph_utils::DIM_F,
+};
+use rayon::prelude::*;
+use utils::{
+    error::SimError,
+    parameters::{DIM, NeighboringList as Neighbor, Particle, Real, VariableSmoothing},
+};
+
+/// Lower bound of the grad-h term (guards against division by a vanishing Omega)
+const MIN_OMEGA: Real = 0.1;
+
+/// Smoothing length of the refinement level of the particle
+fn base_smooth_length(p: &Particle<DIM>, smooth_length: Real) -> Real {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let level = p.level as i32;
+    smooth_length * Real::powi(0.5, level)
+}
+
+/// Update the smoothing lengths, rebuild the neighbor table with the new supports
+/// and apply the grad-h correction. Returns the pair number.
+///
+/// # Errors
+/// Too many pairs or no pair found
+pub(crate) fn update_variable_h(
+    particles: &mut [Particle<DIM>],
+    neighbors: &mut [Neighbor<DIM>],
+    max_pair_n: usize,
+    smooth_length: f64,
+    cell_scale: f64,
+    config: &VariableSmoothing,
+) -> Result<usize, SimError> {
+    update_smoothing_length(particles, smooth_length as Real, config);
+    let k = search_near_particles(particles, neighbors, max_pair_n, smooth_length, cell_scale)?;
+    if config.grad_h {
+        grad_h_correction(particles, &mut neighbors[0..k]);
+    }
+    Ok(k)
+}
+
+/// h_i = h_0 (rho0_i / rho_i)^(1/DIM), bounded by the configured ratios
+fn update_smoothing_length(particles: &mut [Particle<DIM>], smooth_length: Real, config: &VariableSmoothing) {
+    let (min_ratio, max_ratio) = (config.min_ratio as Real, config.max_ratio as Real);
+    particles.par_iter_mut().for_each(|p| {
+        let ratio = (p.rho0 / p.rho).powf(DIM_F.recip()).clamp(min_ratio, max_ratio);
+        p.h = base_smooth_length(p, smooth_length) * ratio;
+    });
+}
+
+/// Grad-h correction: Omega_i = 1 - dh_i/drho_i sum_j m_j dW_ij(h_i)/dh_i with dh/drho = -h / (DIM rho),
+/// then recompute the pair kernel gradients with dW(h_i) / Omega_i.
+fn grad_h_correction(particles: &mut [Particle<DIM>], neighbors: &mut [Neighbor<DIM>]) {
+    // Self contribution (d = 0)
+    let mut sums: Vec<Real> = particles.iter().map(|p| p.rho * p.volume * kernel_dh(0.0, p.h)).collect();
+    for neigh in neighbors.iter().filter(|neigh| neigh.i != neigh.j) {
+        let (pi, pj) = (&particles[neigh.i], &particles[neigh.j]);
+        let d = pi.x.metric_distance(&pj.x);
+        sums[neigh.i] += pj.rho * pj.volume * kernel_dh(d, pi.h);
+    }
+
+    particles.par_iter_mut().zip(sums.par_iter()).for_each(|(p, sum)| {
+        p.omega = (p.h / (DIM_F * p.rho)).mul_add(*sum, 1.0).max(MIN_OMEGA);
+    });
+
+    let particles = &*particles;
+    neighbors.par_iter_mut().filter(|neigh| neigh.i != neigh.j).for_each(|neigh| {
+        let (w, dwdr) = pair_kernel(particles, neigh.i, neigh.j);
+        neigh.w = w;
+        neigh.dwdr = dwdr;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::kernel::cubic_spline;
+    use utils::materials::Material;
+
+    const DX: Real = 0.01;
+    const SMOOTH_LENGTH: f64 = 0.012;
+
+    /// Lattice of 6^DIM particles compressed by `compression` (rho = compression * rho0)
+    fn block(compression: Real) -> Vec<Particle<DIM>> {
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        (0..6_usize.pow(DIM as u32))
+            .map(|index| {
+                let mut p = template.clone();
+                let mut rest = index;
+                for d in 0..DIM {
+                    #[allow(clippy::cast_precision_loss)]
+                    let coordinate = (rest % 6) as Real;
+                    p.x[d] = coordinate * DX;
+                    rest /= 6;
+                }
+                p.rho = compression * p.rho0;
+                p.volume = DX.powi(DIM as i32);
+                p
+            })
+            .collect()
+    }
+
+    fn update(particles: &mut [Particle<DIM>], config: &VariableSmoothing) -> Vec<Neighbor<DIM>> {
+        let max_pairs = particles.len() * 200;
+        let mut neighbors = vec![Neighbor::default(); max_pairs];
+        let k = update_variable_h(particles, &mut neighbors, max_pairs, SMOOTH_LENGTH, 2.0, config).expect("variable h");
+        neighbors.truncate(k);
+        neighbors
+    }
+
+    /// h follows (rho0 / rho)^(1/DIM) within the configured bounds
+    #[test]
+    fn test_h_follows_density() {
+        let config = VariableSmoothing {
+            grad_h: false,
+            ..VariableSmoothing::default()
+        };
+        let h0 = SMOOTH_LENGTH as Real;
+
+        let mut particles = block(1.2);
+        update(&mut particles, &config);
+        let expected = h0 * 1.2_f64.powf(-1.0 / f64::from(DIM_F)) as Real;
+        assert!(particles.iter().all(|p| (p.h - expected).abs() < 1.0e-5 * h0 && p.h < h0));
+
+        let mut particles = block(0.8);
+        update(&mut particles, &config);
+        assert!(particles.iter().all(|p| p.h > h0));
+
+        let mut particles = block(100.0);
+        update(&mut particles, &config);
+        let min = h0 * config.min_ratio as Real;
+        assert!(particles.iter().all(|p| (p.h - min).abs() < 1.0e-5 * h0));
+    }
+
+    /// Omega_i = 1 + h_i / (DIM rho_i) d(sum_j m_j W_ij)/dh_i, checked against a finite difference
+    /// of the density sum, and the pair gradients are divided by it
+    #[test]
+    fn test_grad_h() {
+        let plain = {
+            let mut particles = block(1.2);
+            let neighbors = update(
+                &mut particles,
+                &VariableSmoothing {
+                    grad_h: false,
+                    ..VariableSmoothing::default()
+                },
+            );
+            (particles, neighbors)
+        };
+        let mut particles = block(1.2);
+        let neighbors = update(&mut particles, &VariableSmoothing::default());
+        assert!(particles.iter().all(|p| p.omega >= MIN_OMEGA && (p.omega - 1.0).abs() < 0.5));
+
+        // Density sum of particle i with the smoothing length h
+        let density_sum = |i: usize, h: f64| -> f64 {
+            particles
+                .iter()
+                .map(|pj| {
+                    let d = f64::from(particles[i].x.metric_distance(&pj.x));
+                    f64::from(pj.rho * pj.volume) * cubic_spline(d, h).0
+                })
+                .sum()
+        };
+        // Corner and interior particle
+        let interior = (0..DIM).map(|d| 2 * 6_usize.pow(d as u32)).sum();
+        for i in [0, interior] {
+            let p = &particles[i];
+            let (h, rho) = (f64::from(p.h), f64::from(p.rho));
+            let delta = 1.0e-4 * h;
+            let derivative = (density_sum(i, h + delta) - density_sum(i, h - delta)) / (2.0 * delta);
+            let omega = (h / (f64::from(DIM_F) * rho)).mul_add(derivative, 1.0);
+            assert!((f64::from(p.omega) - omega).abs() < 1.0e-3, "{} != {omega}", p.omega);
+        }
+
+        // Same h for all particles: the gradient is the plain one times (1 / Omega_i + 1 / Omega_j) / 2
+        for (neigh, plain) in neighbors.iter().zip(&plain.1).filter(|(neigh, _)| neigh.i != neigh.j) {
+            assert_eq!((neigh.i, neigh.j), (plain.i, plain.j));
+            let scale = 0.5 * (particles[neigh.i].omega.recip() + particles[neigh.j].omega.recip());
+            assert!((neigh.dwdr - scale * plain.dwdr).norm() <= 1.0e-4 * plain.dwdr.norm());
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::sph_utils::{DIM_F, selected_pairs, velocity_gradient, with_neighbors};
+use rayon::prelude::*;
+use utils::{
+    error::{SimError, check_nan_matrix_to_error},
+    parameters::{DIM, Matrix, NeighboringList as Neighbor, Particle, Phase, Real, SolidConfig, SolidModel, Vector},
+};
+
+/// Exponent of the artificial stress weighting `(W_ij / W(dp))^n`
+const ARTIFICIAL_STRESS_EXPONENT: i32 = 4;
+
+/// Assign the particles to the solids and set their material properties.
+pub(crate) fn init_solids(particles: &mut [Particle<DIM>], configs: &[SolidConfig]) {
+    particles.par_iter_mut().for_each(|p| {
+        if p.is_rigid() {
+            return;
+        }
+        if let Some(s) = configs.iter().position(|config| config.region.contains(p)) {
+            let config = &configs[s];
+            p.phase = Phase::Solid(s);
+            p.rho0 = config.density as Real;
+            p.rho = p.rho0;
+            p.sound_v = config.sound_speed() as Real;
+            p.viscosity = 0.0;
+        }
+    });
+}
+
+/// Deviatoric stress rate with the Jaumann derivative and the von Mises return mapping.
+/// The Cauchy stress of solid particles is replaced by `-p I + S` with a linear equation of state.
+/// Fluid particles interact with solid particles through the common stress divergence.
+/// `dt` = 0 only refreshes the Cauchy stress of the current density.
+/// Only the `selected` particles are updated (all for `None`).
+pub(crate) fn update_solid_stress(
+    dt: f64,
+    particles: &mut [Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    configs: &[SolidConfig],
+    selected: Option<&[bool]>,
+) -> Result<(), SimError> {
+    let grad_v = velocity_gradient(particles, neighbors, selected, |p| matches!(p.phase, Phase::Solid(_)));
+
+    let identity = Matrix::<DIM>::identity();
+    let dt = dt as Real;
+    particles
+        .par_iter_mut()
+        .zip(grad_v.par_iter())
+        .enumerate()
+        .filter(|(i, _)| selected.is_none_or(|selected| selected[*i]))
+        .try_for_each(|(i, (p, grad_v))| {
+            let Phase::Solid(s) = p.phase else {
+                return Ok(());
+            };
+            let config = &configs[s];
+
+            let strain_rate = 0.5 * (grad_v + grad_v.transpose());
+            let spin = 0.5 * (grad_v - grad_v.transpose());
+            let mut deviatoric = p.deviatoric;
+
+            // Jaumann rate: dS/dt = 2G (e' - tr(e')/D I) + S W^T + W S
+            let dev_strain_rate = strain_rate - strain_rate.trace() / DIM_F * identity;
+            let rate =
+                2.0 * config.shear_modulus() as Real * dev_strain_rate + deviatoric * spin.transpose() + spin * deviatoric;
+            deviatoric += dt * rate;
+
+            // von Mises: scale back onto the yield surface
+            if let SolidModel::ElasticPerfectlyPlastic { yield_stress } = config.model {
+                let yield_stress = yield_stress as Real;
+                let j2 = 0.5 * deviatoric.component_mul(&deviatoric).sum();
+                let von_mises = (3.0 * j2).sqrt();
+                if von_mises > yield_stress {
+                    deviatoric *= yield_stress / von_mises;
+                }
+            }
+
+            let pressure = p.sound_v.powi(2) * (p.rho - p.rho0);
+            p.deviatoric = deviatoric;
+            p.stress = deviatoric - pressure * identity;
+            check_nan_matrix_to_error(i, "stress", &p.stress)
+        })
+}
+
+/// Artificial stress (Monaghan 2000, Gray et al. 2001) against the tensile instability of solids
+fn artificial_stress(particle: &Particle<DIM>, epsilon: Real) -> Matrix<DIM> {
+    let eigen = particle.stress.symmetric_eigen();
+    let rho2 = particle.rho.powi(2);
+    let principal = eigen
+        .eigenvalues
+        .map(|sigma| if sigma > 0.0 { -epsilon * sigma / rho2 } else { 0.0 });
+    eigen.eigenvectors * Matrix::<DIM>::from_diagonal(&principal) * eigen.eigenvectors.transpose()
+}
+
+/// Add the artificial stress term to the acceleration of the `active` solid particles (all for `None`).
+/// `w_dp` is the kernel value at the initial particle spacing.
+pub(crate) fn add_artificial_stress(
+    particles: &mut [Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    configs: &[SolidConfig],
+    w_dp: Real,
+    active: Option<&[bool]>,
+) {
+    if w_dp <= 0.0 {
+        return;
+    }
+
+    // Artificial stress of the active particles and their neighbors
+    let needed = active.map(|active| with_neighbors(particles, neighbors, active));
+    let stress: Vec<Option<Matrix<DIM>>> = particles
+        .par_iter()
+        .enumerate()
+        .map(|(i, p)| match p.phase {
+            Phase::Solid(s) if needed.as_ref().is_none_or(|needed| needed[i]) => {
+                Some(artificial_stress(p, configs[s].artificial_stress as Real))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut dvdt = vec![Vector::<DIM>::zeros(); particles.len()];
+    for pair in selected_pairs(particles, neighbors.len(), active).into_iter().flatten() {
+        let neigh = &neighbors[pair];
+        let (i, j) = (neigh.i, neigh.j);
+        if let (Some(r_i), Some(r_j)) = (stress[i], stress[j]) {
+            let f = (neigh.w / w_dp).powi(ARTIFICIAL_STRESS_EXPONENT);
+            let m_j = particles[j].rho * particles[j].volume;
+            dvdt[i] += m_j * f * (r_i + r_j) * neigh.dwdr;
+        }
+    }
+
+    particles.par_iter_mut().zip(dvdt.par_iter()).for_each(|(p, dv)| {
+        p.dvdt += dv;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{materials::Material, parameters::Region};
+
+    const YIELD_STRESS: f64 = 1.0e4;
+
+    fn steel() -> SolidConfig {
+        SolidConfig {
+            region: Region::Tag(0),
+            density: 7800.0,
+            youngs_modulus: 1.0e6,
+            poisson_ratio: 0.3,
+            model: SolidModel::ElasticPerfectlyPlastic {
+                yield_stress: YIELD_STRESS,
+            },
+            artificial_stress: 0.3,
+        }
+    }
+
+    /// Solid particle 0 stretched along x by particle 1 (unit volume, kernel gradient along x):
+    /// uniaxial strain rate `rate`
+    fn stretched_pair(rate: Real, config: &SolidConfig) -> (Vec<Particle<DIM>>, Vec<Neighbor<DIM>>) {
+        let mut solid = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        solid.volume = 1.0;
+        let mut other = solid.clone();
+        other.v[0] = 1.0;
+        init_solids(std::slice::from_mut(&mut solid), std::slice::from_ref(config));
+
+        let mut neighbor = Neighbor {
+            j: 1,
+            ..Default::default()
+        };
+        neighbor.dwdr[0] = rate;
+        (vec![solid, other], vec![neighbor])
+    }
+
+    /// Von Mises stress of a deviatoric stress
+    fn von_mises(deviatoric: &Matrix<DIM>) -> f64 {
+        f64::from(1.5 * deviatoric.component_mul(deviatoric).sum()).sqrt()
+    }
+
+    /// Uniaxial straining: elastic growth of the deviatoric stress at 2G times the deviatoric strain rate,
+    /// then plastic flow with the stress held on the yield surface
+    #[test]
+    fn test_uniaxial_yield() {
+        let config = steel();
+        let configs = std::slice::from_ref(&config);
+        let rate = 10.0;
+        let dt = 1.0e-4;
+        let (mut particles, neighbors) = stretched_pair(rate, &config);
+        assert_eq!(particles[0].phase, Phase::Solid(0));
+
+        update_solid_stress(dt, &mut particles, &neighbors, configs, None).expect("solid stress");
+        let elastic = 2.0 * config.shear_modulus() * f64::from(rate) * dt * (1.0 - 1.0 / f64::from(DIM_F));
+        let s_xx = f64::from(particles[0].deviatoric[(0, 0)]);
+        assert!((s_xx - elastic).abs() < 1.0e-4 * elastic, "{s_xx} != {elastic}");
+        assert!(von_mises(&particles[0].deviatoric) < YIELD_STRESS);
+
+        for _ in 0..50 {
+            update_solid_stress(dt, &mut particles, &neighbors, configs, None).expect("solid stress");
+        }
+        let p = &particles[0];
+        let von_mises = von_mises(&p.deviatoric);
+        assert!((von_mises - YIELD_STRESS).abs() < 1.0e-4 * YIELD_STRESS, "{von_mises}");
+        // Deviatoric and still uniaxial
+        assert!(f64::from(p.deviatoric.trace().abs()) < 1.0e-4 * YIELD_STRESS);
+        let s_yy = -p.deviatoric[(0, 0)] / (DIM_F - 1.0);
+        assert!((p.deviatoric[(1, 1)] - s_yy).abs() < 1.0e-4 * s_yy.abs());
+        // Cauchy stress at the reference density
+        assert!((p.stress - p.deviatoric).norm() < 1.0e-4 * p.deviatoric.norm());
+    }
+
+    /// The artificial stress opposes tensile principal stresses only
+    #[test]
+    fn test_artificial_stress() {
+        let config = steel();
+        let (mut particles, _) = stretched_pair(0.0, &config);
+        let p = &mut particles[0];
+        p.stress = Matrix::<DIM>::from_diagonal(&Vector::<DIM>::from_fn(|d, _| if d == 0 { 1.0e4 } else { -1.0e4 }));
+
+        let epsilon = config.artificial_stress as Real;
+        let r = artificial_stress(p, epsilon);
+        let expected = -epsilon * 1.0e4 / p.rho.powi(2);
+        assert!((r[(0, 0)] - expected).abs() < 1.0e-4 * expected.abs());
+        assert!(r[(1, 1)].abs() < 1.0e-4 * expected.abs());
+        assert!(r[(0, 1)].abs() < 1.0e-4 * expected.abs());
+    }
+}
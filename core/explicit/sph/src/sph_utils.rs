@@ -1,9 +1,67 @@
-use nalgebra::{self as na, SimdComplexField};
+use nalgebra::SimdComplexField;
+use std::ops::Range;
 use utils::{
     error::{SimError, check_nan_to_error},
-    parameters::{DIM, NeighboringList as Neighbor, Particle},
+    parameters::{DIM, Matrix, NeighboringList as Neighbor, Particle, Real},
 };
 
+/// Dimension as a float (trace of the identity)
+#[allow(clippy::cast_precision_loss)]
+pub(crate) const DIM_F: Real = DIM as Real;
+
+/// Velocity gradient L_i = sum_j V_j (v_j - v_i) dW_ij^T of the included particles
+/// among the `selected` ones (all for `None`)
+pub(crate) fn velocity_gradient(
+    particles: &[Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    selected: Option<&[bool]>,
+    include: impl Fn(&Particle<DIM>) -> bool,
+) -> Vec<Matrix<DIM>> {
+    let mut grad_v = vec![Matrix::<DIM>::zeros(); particles.len()];
+    for pair in selected_pairs(particles, neighbors.len(), selected).into_iter().flatten() {
+        let (i, j) = (neighbors[pair].i, neighbors[pair].j);
+        if include(&particles[i]) {
+            let vij = particles[j].v - particles[i].v;
+            grad_v[i] += vij * neighbors[pair].dwdr.transpose() * particles[j].volume;
+        }
+    }
+    grad_v
+}
+
+/// Pairs of particle `i` in the neighbor table of `pairs` entries (grouped by `i`, see `make_neighboring_list`)
+pub(crate) fn pair_range(particles: &[Particle<DIM>], i: usize, pairs: usize) -> Range<usize> {
+    let start = match i {
+        0 => 0,
+        _ => particles[i - 1].pair + 1,
+    };
+    start.min(pairs)..(particles[i].pair + 1).min(pairs)
+}
+
+/// Pairs of the `selected` particles in the order of the table (all pairs for `None`)
+pub(crate) fn selected_pairs(particles: &[Particle<DIM>], pairs: usize, selected: Option<&[bool]>) -> Vec<Range<usize>> {
+    selected.map_or_else(
+        || std::iter::once(0..pairs).collect(),
+        |selected| {
+            (0..particles.len())
+                .filter(|&i| selected[i])
+                .map(|i| pair_range(particles, i, pairs))
+                .collect()
+        },
+    )
+}
+
+/// The `selected` particles and their neighbors
+pub(crate) fn with_neighbors(particles: &[Particle<DIM>], neighbors: &[Neighbor<DIM>], selected: &[bool]) -> Vec<bool> {
+    let mut halo = selected.to_vec();
+    for pair in selected_pairs(particles, neighbors.len(), Some(selected))
+        .into_iter()
+        .flatten()
+    {
+        halo[neighbors[pair].j] = true;
+    }
+    halo
+}
+
 // -- Traits --
 // Standard sph
 pub(crate) trait _SphStd {
@@ -25,14 +83,14 @@ pub(crate) trait SphDiff {
 // Note: Traits are used these only structs.
 #[derive(Debug, PartialEq)]
 pub struct Velocity<const D: usize> {
-    pub grad_v: na::Matrix3<f64>,
-    pub div_v: f64,
+    pub grad_v: Matrix<DIM>,
+    pub div_v: Real,
 }
 
 impl<const D: usize> Velocity<D> {
     pub fn new() -> Self {
         Self {
-            grad_v: na::Matrix3::zeros(),
+            grad_v: Matrix::<DIM>::zeros(),
             div_v: 0.0,
         }
     }
@@ -55,21 +113,15 @@ impl<const D: usize> SphDiff for Velocity<D> {
 
     // Todo: Generic Vector (velocity -> vector3)
     fn sph_div(&mut self, particles: &[Particle<DIM>], neighbors: &[Neighbor<DIM>], i: usize) -> Result<(), SimError> {
-        let start: usize = match i {
-            0 => 0,
-            _ => particles[i - 1].pair + 1,
-        };
-        let end: usize = particles[i].pair;
-
         // Initialize
         *self = Self::new();
 
-        // sph referred to neighboring list (pair: start -> end)
-        for neigh in neighbors.iter().take(end + 1).skip(start) {
+        // sph referred to neighboring list (pairs of i)
+        for neigh in &neighbors[pair_range(particles, i, neighbors.len())] {
             let j = neigh.j;
-            let vi = na::Vector3::from(particles[i].v);
-            let vj = na::Vector3::from(particles[j].v);
-            let dwdr = na::Vector3::from(neigh.dwdr);
+            let vi = particles[i].v;
+            let vj = particles[j].v;
+            let dwdr = neigh.dwdr;
 
             let volume_i = particles[i].volume;
             let volume_j = particles[j].volume;
@@ -77,7 +129,7 @@ impl<const D: usize> SphDiff for Velocity<D> {
             self.div_v += (vi - vj).dot(&dwdr) * volume_j;
             self.div_v += (vj - vi).dot(&-dwdr) * volume_i;
 
-            check_nan_to_error(0, self.div_v)?;
+            check_nan_to_error(i, "div_v", self.div_v)?;
         }
 
         Ok(())
@@ -87,7 +139,7 @@ impl<const D: usize> SphDiff for Velocity<D> {
 // Note: Only working on DIM = 3
 #[derive(Debug, PartialEq)]
 pub struct Tensor<const D: usize> {
-    pub div_tensor: [f64; D],
+    pub div_tensor: [Real; D],
 }
 
 impl Tensor<DIM> {
@@ -112,22 +164,16 @@ impl SphDiff for Tensor<DIM> {
     }
 
     fn sph_div(&mut self, particles: &[Particle<DIM>], neighbors: &[Neighbor<DIM>], i: usize) -> Result<(), Self::Error> {
-        let start: usize = match i {
-            0 => 0,
-            _ => particles[i - 1].pair + 1,
-        };
-        let end: usize = particles[i].pair;
-
         // Initialize
         *self = Self::new();
 
-        // sph referred to neighboring list (pair: start -> end)
-        for neigh in neighbors.iter().take(end + 1).skip(start) {
+        // sph referred to neighboring list (pairs of i)
+        for neigh in &neighbors[pair_range(particles, i, neighbors.len())] {
             let j = neigh.j;
-            let mut tensor_i = na::Matrix3::from(particles[i].stress);
-            let mut tensor_j = na::Matrix3::from(particles[j].stress);
+            let mut tensor_i = particles[i].stress;
+            let mut tensor_j = particles[j].stress;
 
-            let dwdr = na::Vector3::from(neigh.dwdr);
+            let dwdr = neigh.dwdr;
             let volume_i = particles[i].volume;
             let volume_j = particles[j].volume;
 
@@ -140,7 +186,7 @@ impl SphDiff for Tensor<DIM> {
             for d in 0..DIM {
                 self.div_tensor[d] += dot_i[d] * volume_j;
                 self.div_tensor[d] += dot_j[d] * volume_i;
-                check_nan_to_error(d, self.div_tensor[d])?;
+                check_nan_to_error(i, "div_stress", self.div_tensor[d])?;
             }
         }
 
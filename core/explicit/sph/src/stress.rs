@@ -1,13 +1,14 @@
-use super::sph_utils::Velocity;
-use nalgebra::{self as na, SimdComplexField};
+use super::sph_utils::{DIM_F, Velocity, selected_pairs, velocity_gradient};
+use nalgebra::SimdComplexField;
 use rayon::prelude::*;
 use utils::{
-    error::{SimError, check_nan_matrix3_to_error},
-    parameters::{DIM, NeighboringList as Neighbor, Particle},
+    error::{SimError, check_nan_matrix_to_error},
+    materials::MaterialLibrary,
+    parameters::{DIM, GranularConfig, GranularModel, Matrix, NeighboringList as Neighbor, Particle, Phase, Real},
 };
 
 // For water
-fn tait_eq(particle: &Particle<DIM>) -> f64 {
+fn tait_eq(particle: &Particle<DIM>) -> Real {
     let gamma = 7.0; // parameter of Tait eq.
     let b = particle.sound_v.simd_powf(2.0) / gamma; // parameter of Tait eq.
     let rho_ratio = particle.rho / particle.rho0;
@@ -16,33 +17,39 @@ fn tait_eq(particle: &Particle<DIM>) -> f64 {
     particle.rho0 * b * (rho_ratio.simd_powf(gamma) - 1.0)
 }
 
-fn static_stress(particles: &mut [Particle<DIM>]) {
-    let identity: na::Matrix3<f64> = na::Matrix3::identity();
+fn static_stress(particles: &mut [Particle<DIM>], selected: Option<&[bool]>) {
+    let identity = Matrix::<DIM>::identity();
 
-    particles.par_iter_mut().for_each(|particle| {
-        // p = p * identity matrix
-        let p = -tait_eq(particle) * identity;
-        particle.stress += p;
-    });
+    particles
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(i, _)| selected.is_none_or(|selected| selected[*i]))
+        .for_each(|(_, particle)| {
+            // p = p * identity matrix
+            let p = -tait_eq(particle) * identity;
+            particle.stress += p;
+        });
 }
 
 fn viscosity_stress(
     particles: &mut [Particle<DIM>],
-    neighbors: &mut [Neighbor<DIM>],
+    neighbors: &[Neighbor<DIM>],
     diff_velocity: &mut [Velocity<DIM>],
+    selected: Option<&[bool]>,
 ) -> Result<(), SimError> {
     // Total particles and identity matrix
     let n = particles.len();
-    let identity: na::Matrix3<f64> = na::Matrix3::identity();
+    let identity = Matrix::<DIM>::identity();
 
-    for neigh in neighbors.iter_mut() {
+    for pair in selected_pairs(particles, neighbors.len(), selected).into_iter().flatten() {
+        let neigh = &neighbors[pair];
         // Velocity gradient
-        let mut grad_vi = na::Matrix3::zeros();
-        let mut grad_vj = na::Matrix3::zeros();
+        let mut grad_vi = Matrix::<DIM>::zeros();
+        let mut grad_vj = Matrix::<DIM>::zeros();
 
-        let vi = na::Vector3::from(particles[neigh.i].v);
-        let vj = na::Vector3::from(particles[neigh.j].v);
-        let dwdr = na::Vector3::from(neigh.dwdr);
+        let vi = particles[neigh.i].v;
+        let vj = particles[neigh.j].v;
+        let dwdr = neigh.dwdr;
 
         let volume_i = particles[neigh.i].volume;
         let volume_j = particles[neigh.j].volume;
@@ -54,30 +61,264 @@ fn viscosity_stress(
         particles[neigh.i].stress = grad_vi + grad_vi.transpose();
         particles[neigh.j].stress = grad_vj + grad_vj.transpose();
 
-        check_nan_matrix3_to_error(neigh.i, particles[neigh.i].stress)?;
-        check_nan_matrix3_to_error(neigh.j, particles[neigh.j].stress)?;
+        check_nan_matrix_to_error(neigh.i, "stress", &particles[neigh.i].stress)?;
+        check_nan_matrix_to_error(neigh.j, "stress", &particles[neigh.j].stress)?;
     }
 
     // Viscosity stress
-    for (i, v) in diff_velocity.iter_mut().enumerate().take(n) {
+    for (i, v) in diff_velocity
+        .iter_mut()
+        .enumerate()
+        .take(n)
+        .filter(|(i, _)| selected.is_none_or(|selected| selected[*i]))
+    {
         // Viscosity stress
-        particles[i].stress += -&identity * v.div_v * 2.0 / 3.0;
+        particles[i].stress += -&identity * v.div_v * 2.0 / DIM_F;
         particles[i].stress *= particles[i].viscosity;
     }
 
     Ok(())
 }
 
+/// Viscous and pressure stress of the `selected` particles (all for `None`)
+/// # Errors
+/// Nan value occurs
 pub(crate) fn update_stress(
     particles: &mut [Particle<DIM>],
-    neighbors: &mut [Neighbor<DIM>],
+    neighbors: &[Neighbor<DIM>],
     diff_velocity: &mut [Velocity<DIM>],
+    selected: Option<&[bool]>,
 ) -> Result<(), SimError> {
     // Compute viscosity stress
-    viscosity_stress(particles, neighbors, diff_velocity)?;
+    viscosity_stress(particles, neighbors, diff_velocity, selected)?;
 
     // Add static stress
-    static_stress(particles);
+    static_stress(particles, selected);
 
     Ok(())
 }
+
+/// Assign the particles to the granular materials.
+pub(crate) fn init_granular(particles: &mut [Particle<DIM>], configs: &[GranularConfig]) {
+    particles.par_iter_mut().for_each(|p| {
+        if p.phase != Phase::Fluid {
+            return;
+        }
+        if let Some(g) = configs.iter().position(|config| config.region.contains(p)) {
+            p.phase = Phase::Granular(g);
+            p.rho0 = configs[g].density as Real;
+            p.rho = p.rho0;
+        }
+    });
+}
+
+/// Effective viscosity of the regularized granular rheology for the pressure `p`
+/// and the shear rate `shear_rate` = 2 sqrt(J2(D)).
+fn granular_viscosity(config: &GranularConfig, p: f64, rho: f64, shear_rate: f64) -> f64 {
+    let p = p.max(0.0);
+    if shear_rate <= f64::EPSILON {
+        return config.max_viscosity;
+    }
+
+    let yield_stress = match config.model {
+        // sqrt(J2(tau)) = 3 alpha p + k
+        GranularModel::DruckerPrager => {
+            let (alpha, k) = config.drucker_prager();
+            3.0 * alpha.mul_add(p, k / 3.0)
+        }
+        // tau = mu(I) p + c, I = shear_rate * d / sqrt(p / rho)
+        GranularModel::MuI {
+            mu_2,
+            i_0,
+            grain_diameter,
+        } => {
+            let mu_s = config.friction_angle.to_radians().tan();
+            let inertial = shear_rate * grain_diameter / (p / rho).sqrt().max(f64::EPSILON);
+            let mu = (mu_2 - mu_s) / (i_0 / inertial.max(f64::EPSILON) + 1.0) + mu_s;
+            mu.mul_add(p, config.cohesion)
+        }
+    };
+
+    // sqrt(J2(tau)) = eta * shear_rate
+    (yield_stress / shear_rate).min(config.max_viscosity)
+}
+
+/// Granular stress: sigma = -p I + 2 eta(p, shear rate) D' with the Drucker–Prager or mu(I) yield stress.
+/// Yielded particles dilate with the dilatancy angle: d(rho)/dt = -rho tan(psi) shear rate (none for `dt` = 0).
+/// Only the `selected` particles are updated (all for `None`).
+pub(crate) fn update_granular_stress(
+    dt: f64,
+    particles: &mut [Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    configs: &[GranularConfig],
+    selected: Option<&[bool]>,
+) -> Result<(), SimError> {
+    let grad_v = velocity_gradient(particles, neighbors, selected, |p| matches!(p.phase, Phase::Granular(_)));
+    let identity = Matrix::<DIM>::identity();
+    let dt = dt as Real;
+
+    particles
+        .par_iter_mut()
+        .zip(grad_v.par_iter())
+        .enumerate()
+        .filter(|(i, _)| selected.is_none_or(|selected| selected[*i]))
+        .try_for_each(|(i, (p, grad_v))| {
+            let Phase::Granular(g) = p.phase else {
+                return Ok(());
+            };
+            let config = &configs[g];
+
+            let strain_rate = 0.5 * (grad_v + grad_v.transpose());
+            let dev_strain_rate = strain_rate - strain_rate.trace() / DIM_F * identity;
+            let shear_rate = 2.0 * (0.5 * dev_strain_rate.component_mul(&dev_strain_rate).sum()).sqrt();
+
+            let pressure = tait_eq(p);
+            let viscosity = granular_viscosity(config, f64::from(pressure), f64::from(p.rho), f64::from(shear_rate));
+
+            // Plastic flow: dilatancy
+            if viscosity < config.max_viscosity {
+                let tan_psi = config.dilatancy_angle.to_radians().tan() as Real;
+                p.rho -= dt * p.rho * tan_psi * shear_rate;
+            }
+
+            p.viscosity = viscosity as Real;
+            p.stress = 2.0 * p.viscosity * dev_strain_rate - pressure * identity;
+            check_nan_matrix_to_error(i, "stress", &p.stress)
+        })
+}
+
+/// Erosion: sediment particles in contact with fluid whose shear stress exceeds the critical
+/// bed shear stress are entrained and continue as (sediment-laden) fluid particles
+/// with the properties of their fluid material. The density keeps its ratio to the reference density
+/// and the volume follows, so that the mass is conserved.
+pub(crate) fn erode_sediment(
+    particles: &mut [Particle<DIM>],
+    neighbors: &[Neighbor<DIM>],
+    configs: &[GranularConfig],
+    materials: &MaterialLibrary,
+) {
+    let n = particles.len();
+    let mut shear = vec![0.0_f64; n];
+
+    for neigh in neighbors.iter() {
+        let (i, j) = (neigh.i, neigh.j);
+        if matches!(particles[i].phase, Phase::Granular(_)) && particles[j].phase == Phase::Fluid {
+            // Shear stress of the fluid neighbor: sqrt(J2) of the deviatoric stress
+            let stress = particles[j].stress;
+            let deviatoric = stress - stress.trace() / DIM_F * Matrix::<DIM>::identity();
+            shear[i] = shear[i].max(f64::from(0.5 * deviatoric.component_mul(&deviatoric).sum()).sqrt());
+        }
+    }
+
+    particles.par_iter_mut().zip(shear.par_iter()).for_each(|(p, shear)| {
+        if let Phase::Granular(g) = p.phase
+            && let Some(erosion) = &configs[g].erosion
+            && *shear > erosion.critical_shear_stress
+        {
+            p.phase = Phase::Fluid;
+            let mass = p.rho * p.volume;
+            p.set_temperature(materials.get(p.material), f64::from(p.temperature));
+            p.volume = mass / p.rho;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{
+        materials::Material,
+        parameters::{ErosionConfig, Region},
+    };
+
+    fn sand(erosion: Option<ErosionConfig>) -> GranularConfig {
+        GranularConfig {
+            region: Region::Tag(0),
+            density: 2000.0,
+            friction_angle: 30.0,
+            cohesion: 0.0,
+            dilatancy_angle: 10.0,
+            model: GranularModel::DruckerPrager,
+            max_viscosity: 1.0e3,
+            erosion,
+        }
+    }
+
+    /// Compressed sediment particle 0 next to fluid particle 1 moving along x,
+    /// with the kernel gradient `dwdr` along y (shear rate 2 |dwdr| for unit volumes)
+    fn sheared_pair(dwdr: Real) -> (Vec<Particle<DIM>>, Vec<Neighbor<DIM>>) {
+        let mut fluid = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        fluid.volume = 1.0;
+        let mut sediment = fluid.clone();
+        sediment.phase = Phase::Granular(0);
+        sediment.rho0 = 2000.0;
+        sediment.rho = 2000.2;
+        fluid.v[0] = 1.0;
+
+        let mut neighbor = Neighbor {
+            j: 1,
+            ..Default::default()
+        };
+        neighbor.dwdr[1] = -dwdr;
+        (vec![sediment, fluid], vec![neighbor])
+    }
+
+    /// Yielded sediment stays on the Drucker–Prager yield surface and dilates; unyielded sediment does neither
+    #[test]
+    fn test_granular_yield() {
+        let config = sand(None);
+        let (alpha, k) = config.drucker_prager();
+
+        let (mut particles, neighbors) = sheared_pair(1000.0);
+        let pressure = f64::from(tait_eq(&particles[0]));
+        let rho = particles[0].rho;
+        update_granular_stress(1.0e-6, &mut particles, &neighbors, std::slice::from_ref(&config), None)
+            .expect("granular stress");
+
+        let p = &particles[0];
+        assert!(f64::from(p.viscosity) < config.max_viscosity, "viscosity = {}", p.viscosity);
+        let deviatoric = p.stress - p.stress.trace() / DIM_F * Matrix::<DIM>::identity();
+        let sqrt_j2 = f64::from(0.5 * deviatoric.component_mul(&deviatoric).sum()).sqrt();
+        let yield_stress = 3.0 * alpha.mul_add(pressure, k / 3.0);
+        assert!(
+            (sqrt_j2 - yield_stress).abs() < 1.0e-4 * yield_stress,
+            "{sqrt_j2} != {yield_stress}"
+        );
+        assert!(p.rho < rho, "rho = {}", p.rho);
+
+        let (mut particles, neighbors) = sheared_pair(1.0e-3);
+        update_granular_stress(1.0e-6, &mut particles, &neighbors, std::slice::from_ref(&config), None)
+            .expect("granular stress");
+        assert!((f64::from(particles[0].viscosity) - config.max_viscosity).abs() < 1.0e-9);
+        assert!((particles[0].rho - rho).abs() <= Real::EPSILON * rho);
+    }
+
+    /// Sediment under a fluid shear stress above the critical one becomes fluid of its material
+    #[test]
+    fn test_erosion() {
+        let water = Material::water();
+        let rho0 = water.density.eval(293.15);
+        let erosion = |critical_shear_stress| {
+            let (mut particles, neighbors) = sheared_pair(0.0);
+            particles[1].stress[(0, 1)] = 10.0;
+            particles[1].stress[(1, 0)] = 10.0;
+            let config = sand(Some(ErosionConfig { critical_shear_stress }));
+            erode_sediment(&mut particles, &neighbors, &[config], &MaterialLibrary::default());
+            particles.swap_remove(0)
+        };
+        let (sediment, _) = sheared_pair(0.0);
+        let mass = f64::from(sediment[0].rho * sediment[0].volume);
+
+        let kept = erosion(20.0);
+        assert_eq!(kept.phase, Phase::Granular(0));
+
+        let eroded = erosion(5.0);
+        assert_eq!(eroded.phase, Phase::Fluid);
+        assert!((f64::from(eroded.rho0) - rho0).abs() < 1.0e-3);
+        assert!((f64::from(eroded.rho / eroded.rho0) - 1.0001).abs() < 1.0e-5);
+        assert!((f64::from(eroded.rho * eroded.volume) - mass).abs() < 1.0e-5 * mass);
+        assert!(eroded.volume > sediment[0].volume);
+        let viscosity = water.viscosity.eval(293.15);
+        assert!((f64::from(eroded.viscosity) - viscosity).abs() < 1.0e-4 * viscosity);
+    }
+}
@@ -1,27 +1,40 @@
 use rayon::prelude::*;
 use utils::{
     error::{SimError, check_nan_to_error},
-    parameters::{DIM, Particle},
+    parameters::{DIM, Particle, Real},
 };
 
-pub(crate) fn update_half_velocity(dt: f64, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
-    particles.par_iter_mut().try_for_each(|particle| {
-        for i in 0..DIM {
-            // half increment
-            particle.v[i] += 0.5 * particle.dvdt[i] * dt;
-            check_nan_to_error(i, particle.v[i])?;
-        }
-        Ok(())
-    })
+// Note: rigid body particles are moved by `update_rigid_bodies`.
+/// Kick of the velocity and the energy over `dt`
+pub(crate) fn update_velocity(dt: f64, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
+    let dt = dt as Real;
+    particles
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(_, p)| !p.is_rigid())
+        .try_for_each(|(i, particle)| {
+            for d in 0..DIM {
+                // increment
+                particle.v[d] += particle.dvdt[d] * dt;
+                check_nan_to_error(i, "v", particle.v[d])?;
+            }
+            particle.e = particle.dedt.mul_add(dt, particle.e);
+            Ok(())
+        })
 }
 
 pub(crate) fn update_location(dt: f64, particles: &mut [Particle<DIM>]) -> Result<(), SimError> {
-    particles.par_iter_mut().try_for_each(|particle| {
-        for i in 0..DIM {
-            // increment
-            particle.x[i] += particle.v[i] * dt;
-            check_nan_to_error(i, particle.x[i])?;
-        }
-        Ok(())
-    })
+    let dt = dt as Real;
+    particles
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(_, p)| !p.is_rigid())
+        .try_for_each(|(i, particle)| {
+            for d in 0..DIM {
+                // increment
+                particle.x[d] += particle.v[d] * dt;
+                check_nan_to_error(i, "x", particle.x[d])?;
+            }
+            Ok(())
+        })
 }
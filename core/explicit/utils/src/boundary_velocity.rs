@@ -0,0 +1,96 @@
+use crate::{
+    error::{FailedFeatureReadFileSnafu, SimError},
+    expression::Expression,
+    parameters::VelocityFunction,
+};
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use snafu::ResultExt as _;
+
+// Structure to hold a time series record
+#[derive(Debug, Deserialize)]
+struct TimeSeries {
+    t: f64,
+    u: f64,
+}
+
+// Function to read a velocity time series
+fn read_time_series(file_path: &std::path::Path) -> Result<Vec<(f64, f64)>, SimError> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_path(file_path)
+        .with_context(|_| FailedFeatureReadFileSnafu {
+            path: file_path.to_path_buf(),
+        })?;
+
+    let mut table = Vec::new();
+    for result in rdr.deserialize() {
+        let record: TimeSeries = result.with_context(|_| FailedFeatureReadFileSnafu {
+            path: file_path.to_path_buf(),
+        })?;
+        table.push((record.t, record.u));
+    }
+    table.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    Ok(table)
+}
+
+/// Evaluator of a `VelocityFunction` (tables loaded and expressions parsed once).
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoundaryVelocity {
+    Constant,
+    Ramp { duration: f64 },
+    Sine { frequency: f64, phase: f64 },
+    Table(Vec<(f64, f64)>),
+    Expression(Expression),
+}
+
+impl BoundaryVelocity {
+    /// # Errors
+    /// Table file cannot be read or expression cannot be parsed
+    pub fn new(function: &VelocityFunction) -> Result<Self, SimError> {
+        Ok(match function {
+            VelocityFunction::Constant => Self::Constant,
+            VelocityFunction::Ramp { duration } => Self::Ramp { duration: *duration },
+            VelocityFunction::Sine { frequency, phase } => Self::Sine {
+                frequency: *frequency,
+                phase: *phase,
+            },
+            VelocityFunction::Table { path } => Self::Table(read_time_series(path)?),
+            VelocityFunction::Expression(src) => Self::Expression(Expression::parse(src)?),
+        })
+    }
+
+    /// Velocity at time `t` and location `x` for the configured velocity `u`.
+    pub fn eval(&self, u: f64, t: f64, x: &[f64]) -> f64 {
+        match self {
+            Self::Ramp { duration } if *duration > 0.0 => u * (t / duration).min(1.0),
+            Self::Constant | Self::Ramp { .. } => u,
+            Self::Sine { frequency, phase } => u * (2.0 * std::f64::consts::PI * frequency).mul_add(t, *phase).sin(),
+            Self::Table(table) => interpolate(table, t),
+            Self::Expression(expr) => expr.eval(u, t, x),
+        }
+    }
+}
+
+/// Linear interpolation, held constant outside the table range.
+fn interpolate(table: &[(f64, f64)], t: f64) -> f64 {
+    let (Some(first), Some(last)) = (table.first(), table.last()) else {
+        return 0.0;
+    };
+    if t <= first.0 {
+        return first.1;
+    }
+    if t >= last.0 {
+        return last.1;
+    }
+
+    let upper = table.partition_point(|(ti, _)| *ti <= t);
+    let (t0, u0) = table[upper - 1];
+    let (t1, u1) = table[upper];
+    if t1 - t0 <= 0.0 {
+        return u1;
+    }
+    (u1 - u0).mul_add((t - t0) / (t1 - t0), u0)
+}
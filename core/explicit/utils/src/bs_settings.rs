@@ -1,11 +1,29 @@
-use super::parameters::{BC, DIM, ModelScale, Particle, Resolution};
+use super::{
+    boundary_velocity::BoundaryVelocity,
+    dimension::to_vector3,
+    parameters::{BC, DIM, ModelScale, Particle, Real, Resolution},
+};
 use rayon::prelude::*;
 
+/// Lid (driving) velocity at a given time
+#[derive(Clone, Copy)]
+pub struct LidVelocity<'a> {
+    pub u_lid: f64,
+    pub function: &'a BoundaryVelocity,
+    pub time: f64,
+}
+
+impl LidVelocity<'_> {
+    fn at(&self, p: &Particle<DIM>) -> f64 {
+        self.function.eval(self.u_lid, self.time, to_vector3(&p.x).as_slice())
+    }
+}
+
 /// # Errors
 pub fn boundary_condition(
     particles: &mut [Particle<DIM>],
     pattern: BC,
-    u_lid: f64,
+    u_lid: LidVelocity,
     model_scale: ModelScale,
     resolution: Resolution,
     smooth_length: f64,
@@ -28,7 +46,7 @@ pub fn boundary_condition(
 /// Cavity flow
 pub fn cavity_flow(
     particles: &mut [Particle<DIM>],
-    u_lid: f64,
+    u_lid: LidVelocity,
     model_scale: ModelScale,
     dx: Resolution,
     smooth_length: f64,
@@ -37,43 +55,36 @@ pub fn cavity_flow(
     let Resolution { dx, dy, dz } = dx;
 
     particles.par_iter_mut().for_each(|p| {
-        let x = p.x[0];
-        let y = p.x[1];
-        let z = p.x[2];
+        let (x, y, z) = p.axis();
 
         if y > width - smooth_length {
-            p.v[0] = u_lid;
-            p.v[1] = 0.0;
-            p.v[2] = 0.0;
-        } else if !(dx..=length - dx).contains(&x) || y < dy || !(dz..=height - dz).contains(&z) {
-            p.v[0] = 0.0;
-            p.v[1] = 0.0;
-            p.v[2] = 0.0;
+            let u = u_lid.at(p);
+            p.v.fill(0.0);
+            p.v[0] = u as Real;
+        } else if !(dx..=length - dx).contains(&x) || y < dy || (DIM == 3 && !(dz..=height - dz).contains(&z)) {
+            p.v.fill(0.0);
         }
     });
 }
 
 /// Poiseuille Flow
-pub fn poiseuille_flow(particles: &mut [Particle<DIM>], u_lid: f64, width: f64, dy: f64) {
+pub fn poiseuille_flow(particles: &mut [Particle<DIM>], u_lid: LidVelocity, width: f64, dy: f64) {
     particles.par_iter_mut().for_each(|p| {
-        let y = p.x[1];
+        let y = f64::from(p.x[1]);
 
-        p.v[0] = 4.0 * u_lid * y * (width - y) / (width * width);
-        p.v[1] = 0.0;
-        p.v[2] = 0.0;
+        let u = 4.0 * u_lid.at(p) * y * (width - y) / (width * width);
+        p.v.fill(0.0);
 
         // no-slip
-        if !(dy..=width - dy).contains(&y) {
-            p.v[0] = 0.0;
-            p.v[1] = 0.0;
-            p.v[2] = 0.0;
+        if (dy..=width - dy).contains(&y) {
+            p.v[0] = u as Real;
         }
     });
 }
 
 /// Periodic flow
 pub fn periodic_flow(particles: &mut [Particle<DIM>], length: f64) {
-    let x_max = length;
+    let x_max = length as Real;
     particles.par_iter_mut().for_each(|p| {
         if p.x[0] < 0.0 {
             p.x[0] += x_max;
@@ -84,15 +95,11 @@ pub fn periodic_flow(particles: &mut [Particle<DIM>], length: f64) {
 }
 
 // Lid-driven cavity
-pub fn lid_driven_cavity(particles: &mut [Particle<DIM>], u_lid: f64, width: f64, smooth_length: f64) {
+pub fn lid_driven_cavity(particles: &mut [Particle<DIM>], u_lid: LidVelocity, width: f64, smooth_length: f64) {
     particles.par_iter_mut().for_each(|p| {
-        let y = p.x[1];
-        if y > width - smooth_length {
-            p.v[0] = u_lid;
-        } else {
-            p.v[0] = 0.0;
-        }
-        p.v[1] = 0.0;
-        p.v[2] = 0.0;
+        let y = f64::from(p.x[1]);
+        let u = if y > width - smooth_length { u_lid.at(p) } else { 0.0 };
+        p.v.fill(0.0);
+        p.v[0] = u as Real;
     });
 }
@@ -1,29 +1,145 @@
-use super::parameters::{DIM, Particle};
-
-pub fn cfl_dt(dt: f64, particles: &[Particle<DIM>], smooth_length: f64) -> f64 {
-    // get max velocity
-    let v_max: Option<f64> = particles
-        .iter()
-        // flat particle velocity
-        .flat_map(|particle| particle.v.iter())
-        // searching max velocity
-        .fold(None, |max, &x| match max {
-            Some(current_max) => Some(current_max.max(x)),
-            None => Some(x),
-        });
+use super::{
+    error::SimError,
+    parameters::{DIM, Particle, TimeStepConfig, TimeStepCriterion},
+};
+use rayon::prelude::*;
+
+/// Largest stable time step of a particle and its limiting criterion
+pub fn particle_dt(p: &Particle<DIM>, config: &TimeStepConfig) -> (f64, TimeStepCriterion) {
+    let h = f64::from(p.h);
+    let speed = f64::from(p.sound_v + p.v.norm());
+    let mut limit = (config.cfl * h / speed, TimeStepCriterion::Acoustic);
+    let mut limit_by = |dt: f64, criterion: TimeStepCriterion| {
+        if dt < limit.0 {
+            limit = (dt, criterion);
+        }
+    };
+
+    // Kinematic viscosity nu = mu / rho
+    if p.viscosity > 0.0 {
+        limit_by(
+            config.viscous * h * h * f64::from(p.rho / p.viscosity),
+            TimeStepCriterion::Viscous,
+        );
+    }
+    if let Some(alpha) = config.thermal_diffusivity.filter(|&alpha| alpha > 0.0) {
+        limit_by(config.thermal * h * h / alpha, TimeStepCriterion::Thermal);
+    }
+    // Rigid particles carry the fluid force on the body, not their own motion
+    let a = f64::from(p.dvdt.norm());
+    if !p.is_rigid() && a > 0.0 {
+        limit_by(config.force * (h / a).sqrt(), TimeStepCriterion::Force);
+    }
+    limit
+}
 
-    // get max sound-velocity
-    let max_sound_v: Option<f64> = particles.iter().fold(None, |max, particle| match max {
-        Some(current_max) => Some(current_max.max(particle.sound_v)),
-        None => Some(particle.sound_v),
-    });
+/// Next time step: the smallest stability limit of the particles, at most `max_growth` times
+/// the previous `dt` and `max_dt`.
+///
+/// With individual time steps it is the step of the coarsest level: the largest limit of the particles
+/// as long as the finest level dt / 2^(levels - 1) resolves the smallest one.
+/// # Errors
+/// The time step of the finest level falls below `min_dt` (or is Nan)
+pub fn adaptive_dt(
+    dt: f64,
+    max_dt: f64,
+    particles: &[Particle<DIM>],
+    config: &TimeStepConfig,
+) -> Result<(f64, TimeStepCriterion), SimError> {
+    let (smallest, largest) = particles
+        .par_iter()
+        .filter(|p| p.h > 0.0)
+        .map(|p| {
+            let limit = particle_dt(p, config);
+            (limit, limit)
+        })
+        .reduce(
+            || ((f64::INFINITY, TimeStepCriterion::Maximum), (0.0, TimeStepCriterion::Maximum)),
+            |(min_a, max_a), (min_b, max_b)| {
+                (
+                    if min_b.0 < min_a.0 { min_b } else { min_a },
+                    if max_b.0 > max_a.0 { max_b } else { max_a },
+                )
+            },
+        );
 
-    // Calculate the new time step dt
-    let new_dt = match (v_max, max_sound_v) {
-        (Some(a), Some(b)) if (a + b) > 0.0 => 0.3 * smooth_length / (a + b),
-        _ => dt,
+    #[allow(clippy::cast_precision_loss)]
+    let substeps = config.substeps() as f64;
+    let coarsest = (smallest.0 * substeps, smallest.1);
+    let (mut new_dt, mut criterion) = if largest.0 > 0.0 && largest.0 < coarsest.0 {
+        largest
+    } else {
+        coarsest
     };
 
-    // the CFL condition
-    f64::min(dt, new_dt)
+    if new_dt > dt * config.max_growth {
+        (new_dt, criterion) = (dt * config.max_growth, TimeStepCriterion::Growth);
+    }
+    if new_dt > max_dt {
+        (new_dt, criterion) = (max_dt, TimeStepCriterion::Maximum);
+    }
+    if new_dt.is_nan() || new_dt / substeps < config.min_dt {
+        return Err(SimError::TimeStepTooSmall {
+            dt: new_dt / substeps,
+            min_dt: config.min_dt,
+            criterion,
+        });
+    }
+    Ok((new_dt, criterion))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Material;
+
+    /// The acoustic criterion limits water at rest; growth and bounds apply on top of it
+    #[test]
+    fn test_adaptive_dt() {
+        let mut p = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        p.h = 0.01;
+        let config = TimeStepConfig::default();
+        let acoustic = config.cfl * f64::from(p.h) / f64::from(p.sound_v);
+
+        let (dt, criterion) = adaptive_dt(1.0, 1.0, std::slice::from_ref(&p), &config).expect("time step");
+        assert_eq!(criterion, TimeStepCriterion::Acoustic);
+        assert!((dt - acoustic).abs() < 1.0e-15);
+
+        let (dt, criterion) = adaptive_dt(1.0e-7, 1.0, std::slice::from_ref(&p), &config).expect("time step");
+        assert_eq!(criterion, TimeStepCriterion::Growth);
+        assert!((dt - 1.1e-7).abs() < 1.0e-15);
+
+        p.dvdt[1] = 1.0e16;
+        let result = adaptive_dt(1.0, 1.0, std::slice::from_ref(&p), &config);
+        assert!(matches!(
+            result,
+            Err(SimError::TimeStepTooSmall {
+                criterion: TimeStepCriterion::Force,
+                ..
+            })
+        ));
+    }
+
+    /// The thermal criterion uses its own diffusion number
+    #[test]
+    fn test_thermal_dt() {
+        let mut p = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        p.h = 0.01;
+        p.viscosity = 0.0;
+        let alpha = 10.0;
+        let config = TimeStepConfig {
+            thermal_diffusivity: Some(alpha),
+            thermal: 0.05,
+            ..TimeStepConfig::default()
+        };
+        let h = f64::from(p.h);
+
+        let (dt, criterion) = particle_dt(&p, &config);
+        assert_eq!(criterion, TimeStepCriterion::Thermal);
+        assert!((dt - config.thermal * h * h / alpha).abs() < 1.0e-15);
+
+        let config = TimeStepConfig { thermal: 0.1, ..config };
+        let (dt, _) = particle_dt(&p, &config);
+        assert!((dt - config.thermal * h * h / alpha).abs() < 1.0e-15);
+    }
 }
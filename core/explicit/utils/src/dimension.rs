@@ -0,0 +1,39 @@
+//! Model vectors and tensors (`DIM` components) in 3D space, see [`AXES`].
+//! Configurations, reports and outputs are 3D `f64`; 2D models lie in the x-y plane.
+use crate::parameters::{AXES, DIM, Matrix, Real, Vector};
+use nalgebra as na;
+
+/// 3D vector of a model vector (zero out of the plane)
+pub fn to_vector3(x: &Vector<DIM>) -> na::Vector3<f64> {
+    let mut v = na::Vector3::zeros();
+    for (d, &axis) in AXES.iter().enumerate() {
+        v[axis] = f64::from(x[d]);
+    }
+    v
+}
+
+/// Model vector of a 3D vector (out-of-plane component dropped)
+pub fn from_vector3(v: &na::Vector3<f64>) -> Vector<DIM> {
+    Vector::<DIM>::from_fn(|d, _| v[AXES[d]] as Real)
+}
+
+/// Model vector of a 3D configuration array
+pub fn from_array3(v: &[f64; 3]) -> Vector<DIM> {
+    Vector::<DIM>::from_fn(|d, _| v[AXES[d]] as Real)
+}
+
+/// 3D tensor of a model tensor (zero out of the plane)
+pub fn to_matrix3(m: &Matrix<DIM>) -> na::Matrix3<f64> {
+    let mut t = na::Matrix3::zeros();
+    for (r, &row) in AXES.iter().enumerate() {
+        for (c, &col) in AXES.iter().enumerate() {
+            t[(row, col)] = f64::from(m[(r, c)]);
+        }
+    }
+    t
+}
+
+/// Model tensor of a 3D tensor (out-of-plane components dropped)
+pub fn from_matrix3(t: &na::Matrix3<f64>) -> Matrix<DIM> {
+    Matrix::<DIM>::from_fn(|r, c| t[(AXES[r], AXES[c])] as Real)
+}
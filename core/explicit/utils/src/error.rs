@@ -15,8 +15,27 @@ pub enum SimError {
     /// Total pair particle is zero.
     ZeroParticleNumber,
 
-    /// Failed: Nan value is detected: v{i}.
-    CannotContinueSimNan { i: usize },
+    /// Failed: Nan value is detected: {field} of particle {i}.
+    CannotContinueSimNan { i: usize, field: &'static str },
+
+    /// Simulation blew up.
+    #[snafu(display("Nan {field} of particle {i} at step {step}, time {time:e} [s]: {neighbors}"))]
+    SimulationBlowUp {
+        step: usize,
+        time: f64,
+        i: usize,
+        field: &'static str,
+        /// Summary of the neighbors of the particle
+        neighbors: String,
+    },
+
+    /// Time step below the minimum.
+    #[snafu(display("Time step {dt:e} [s] below the minimum {min_dt:e} [s] ({criterion:?} criterion)"))]
+    TimeStepTooSmall {
+        dt: f64,
+        min_dt: f64,
+        criterion: crate::parameters::TimeStepCriterion,
+    },
 
     /// None value is detected: x{i}
     FailedUpdateLocation { i: usize },
@@ -37,26 +56,42 @@ pub enum SimError {
     #[snafu(display("Failed to read postcard file: {}", path.display()))]
     PostcardError { source: postcard::Error, path: PathBuf },
 
+    /// Failed to parse material library.
+    #[snafu(display("Failed to parse material library {}: {reason}", path.display()))]
+    FailedParseMaterials { path: PathBuf, reason: String },
+
+    /// Unknown material: {name}
+    UnknownMaterial { name: String },
+
+    /// Invalid expression.
+    #[snafu(display("Invalid expression `{expression}`: {reason}"))]
+    InvalidExpression { expression: String, reason: String },
+
+    /// Hook failed: {message}
+    HookFailed { message: String },
+
     /// Failed: conservative smoothing.
     FailedConservativeSmoothing,
 }
 
 /// # Errors
-pub const fn check_nan_to_error(i: usize, value: f64) -> Result<(), SimError> {
+/// `field` of particle `i` is Nan
+pub const fn check_nan_to_error(i: usize, field: &'static str, value: crate::parameters::Real) -> Result<(), SimError> {
     if value.is_nan() {
-        return Err(SimError::CannotContinueSimNan { i });
+        return Err(SimError::CannotContinueSimNan { i, field });
     }
     Ok(())
 }
 
 /// # Errors
-pub fn check_nan_matrix3_to_error(i: usize, value: nalgebra::Matrix3<f64>) -> Result<(), SimError> {
-    for row in 0..3 {
-        for col in 0..3 {
-            if value[(row, col)].is_nan() {
-                return Err(SimError::CannotContinueSimNan { i });
-            }
-        }
+/// `field` of particle `i` has a Nan component
+pub fn check_nan_matrix_to_error<const D: usize>(
+    i: usize,
+    field: &'static str,
+    value: &crate::parameters::Matrix<D>,
+) -> Result<(), SimError> {
+    if value.iter().any(|value| value.is_nan()) {
+        return Err(SimError::CannotContinueSimNan { i, field });
     }
     Ok(())
 }
@@ -0,0 +1,343 @@
+//! Small expression language for boundary velocities, e.g. `5.0*min(t/0.1,1)`.
+//!
+//! - variables: `t` (time [s]), `x`, `y`, `z` (location [m]), `u` (configured velocity, e.g. `u_lid`)
+//! - constants: `pi`
+//! - operators: `+ - * / ^` and parentheses
+//! - functions: `min, max, pow, sin, cos, tan, tanh, exp, ln, sqrt, abs`
+use crate::error::SimError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    T,
+    X,
+    Y,
+    Z,
+    U,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Func {
+    Min,
+    Max,
+    Pow,
+    Sin,
+    Cos,
+    Tan,
+    Tanh,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "pow" => Self::Pow,
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "tanh" => Self::Tanh,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            _ => return None,
+        })
+    }
+
+    const fn arity(self) -> usize {
+        match self {
+            Self::Min | Self::Max | Self::Pow => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Num(f64),
+    Var(Var),
+    Neg(Box<Self>),
+    Bin(Op, Box<Self>, Box<Self>),
+    Call(Func, Vec<Self>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        match c {
+            ' ' | '\t' => pos += 1,
+            '0'..='9' | '.' => {
+                let start = pos;
+                while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                    pos += 1;
+                }
+                // exponent: 1e-3, 2.5E+2
+                if pos < chars.len() && matches!(chars[pos], 'e' | 'E') {
+                    let mut end = pos + 1;
+                    if end < chars.len() && matches!(chars[end], '+' | '-') {
+                        end += 1;
+                    }
+                    if end < chars.len() && chars[end].is_ascii_digit() {
+                        pos = end;
+                        while pos < chars.len() && chars[pos].is_ascii_digit() {
+                            pos += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..pos].iter().collect();
+                let value = text.parse().map_err(|_| format!("invalid number `{text}`"))?;
+                tokens.push(Token::Num(value));
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let start = pos;
+                while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                    pos += 1;
+                }
+                tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+            }
+            _ => {
+                tokens.push(match c {
+                    '+' => Token::Op(Op::Add),
+                    '-' => Token::Op(Op::Sub),
+                    '*' => Token::Op(Op::Mul),
+                    '/' => Token::Op(Op::Div),
+                    '^' => Token::Op(Op::Pow),
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    ',' => Token::Comma,
+                    _ => return Err(format!("unexpected character `{c}`")),
+                });
+                pos += 1;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == *expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?}, found end of input")),
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Node, String> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(op @ (Op::Add | Op::Sub))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            lhs = Node::Bin(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Node, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op @ (Op::Mul | Op::Div))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            lhs = Node::Bin(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(Token::Op(Op::Sub)) => {
+                self.pos += 1;
+                Ok(Node::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Op(Op::Add)) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    // power := atom ('^' unary)?
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.atom()?;
+        if matches!(self.peek(), Some(Token::Op(Op::Pow))) {
+            self.pos += 1;
+            return Ok(Node::Bin(Op::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    // atom := number | variable | function '(' args ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Num(value)) => Ok(Node::Num(value)),
+            Some(Token::LParen) => {
+                let node = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(node)
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(func) = Func::from_name(&name) {
+                    self.expect(&Token::LParen)?;
+                    let mut args = vec![self.expr()?];
+                    while matches!(self.peek(), Some(Token::Comma)) {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                    self.expect(&Token::RParen)?;
+                    if args.len() != func.arity() {
+                        return Err(format!("`{name}` takes {} argument(s), got {}", func.arity(), args.len()));
+                    }
+                    return Ok(Node::Call(func, args));
+                }
+                Ok(match name.as_str() {
+                    "t" => Node::Var(Var::T),
+                    "x" => Node::Var(Var::X),
+                    "y" => Node::Var(Var::Y),
+                    "z" => Node::Var(Var::Z),
+                    "u" => Node::Var(Var::U),
+                    "pi" => Node::Num(std::f64::consts::PI),
+                    _ => return Err(format!("unknown identifier `{name}`")),
+                })
+            }
+            Some(token) => Err(format!("unexpected token {token:?}")),
+            None => Err("unexpected end of input".into()),
+        }
+    }
+}
+
+/// Parsed expression of `t`, `x`, `y`, `z` and `u`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// # Errors
+    /// Syntax error, unknown identifier or wrong number of function arguments
+    pub fn parse(src: &str) -> Result<Self, SimError> {
+        let invalid = |reason: String| SimError::InvalidExpression {
+            expression: src.to_string(),
+            reason,
+        };
+
+        let tokens = tokenize(src).map_err(invalid)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.expr().map_err(invalid)?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected token {token:?}")));
+        }
+        Ok(Self { root })
+    }
+
+    /// Evaluate with configured velocity `u`, time `t` and location `x`.
+    pub fn eval(&self, u: f64, t: f64, x: &[f64]) -> f64 {
+        eval_node(&self.root, u, t, x)
+    }
+}
+
+fn eval_node(node: &Node, u: f64, t: f64, x: &[f64]) -> f64 {
+    let coord = |d: usize| x.get(d).copied().unwrap_or(0.0);
+    match node {
+        Node::Num(value) => *value,
+        Node::Var(Var::T) => t,
+        Node::Var(Var::X) => coord(0),
+        Node::Var(Var::Y) => coord(1),
+        Node::Var(Var::Z) => coord(2),
+        Node::Var(Var::U) => u,
+        Node::Neg(node) => -eval_node(node, u, t, x),
+        Node::Bin(op, lhs, rhs) => {
+            let (a, b) = (eval_node(lhs, u, t, x), eval_node(rhs, u, t, x));
+            match op {
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div => a / b,
+                Op::Pow => a.powf(b),
+            }
+        }
+        Node::Call(func, args) => {
+            let a = eval_node(&args[0], u, t, x);
+            let b = || eval_node(&args[1], u, t, x);
+            match func {
+                Func::Min => a.min(b()),
+                Func::Max => a.max(b()),
+                Func::Pow => a.powf(b()),
+                Func::Sin => a.sin(),
+                Func::Cos => a.cos(),
+                Func::Tan => a.tan(),
+                Func::Tanh => a.tanh(),
+                Func::Exp => a.exp(),
+                Func::Ln => a.ln(),
+                Func::Sqrt => a.sqrt(),
+                Func::Abs => a.abs(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression_eval() {
+        let ramp = Expression::parse("5.0*min(t/0.1,1)").unwrap();
+        assert!((ramp.eval(0.0, 0.05, &[0.0; 3]) - 2.5).abs() < 1e-12);
+        assert!((ramp.eval(0.0, 1.0, &[0.0; 3]) - 5.0).abs() < 1e-12);
+
+        let wave = Expression::parse("u * sin(2*pi*t) + -x^2 + 1e-1*y").unwrap();
+        let value = wave.eval(2.0, 0.25, &[3.0, 10.0, 0.0]);
+        assert!((value - (2.0 - 9.0 + 1.0)).abs() < 1e-12);
+
+        assert!(Expression::parse("min(t)").is_err());
+        assert!(Expression::parse("5 * w").is_err());
+        assert!(Expression::parse("(t + 1").is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::parameters::DIM;
+use std::f64::consts::PI;
+
+/// Normalization sigma_D of the cubic spline
+#[cfg(not(feature = "dim2"))]
+pub const KERNEL_NORM: f64 = 1.0 / PI;
+#[cfg(feature = "dim2")]
+pub const KERNEL_NORM: f64 = 10.0 / (7.0 * PI);
+
+/// Normalized cubic spline, W = sigma_D b(q) / h^D with q = d / h.
+/// Returns the kernel and its radial derivative dW/dd.
+pub fn cubic_spline(d: f64, h: f64) -> (f64, f64) {
+    let q = d / h;
+    let (b, dbdq) = match q {
+        0.0..=1.0 => (
+            (0.75 * q * q).mul_add(q, 1.5_f64.mul_add(-q * q, 1.0)),
+            (2.25 * q).mul_add(q, -3.0 * q),
+        ),
+        1.0..=2.0 => (0.25 * (2.0 - q).powi(3), -0.75 * (2.0 - q).powi(2)),
+        _ => (0.0, 0.0),
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let sigma = KERNEL_NORM / h.powi(DIM as i32);
+    (sigma * b, sigma * dbdq / h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kernel sums to one over a lattice of spacing dx < h
+    #[test]
+    fn test_kernel_normalization() {
+        let (dx, h) = (0.1, 0.13);
+        let n = 5_i32;
+        let points = (2 * n + 1).pow(DIM as u32);
+        let sum: f64 = (0..points)
+            .map(|k| {
+                let d2: f64 = (0..DIM)
+                    .map(|d| {
+                        let index = k / (2 * n + 1).pow(d as u32) % (2 * n + 1) - n;
+                        (f64::from(index) * dx).powi(2)
+                    })
+                    .sum();
+                cubic_spline(d2.sqrt(), h).0 * dx.powi(DIM as i32)
+            })
+            .sum();
+        assert!((sum - 1.0).abs() < 1.0e-2, "sum = {sum}");
+    }
+
+    /// 2D normalization: the integral of 2 pi r W(r) over the support is one
+    #[cfg(feature = "dim2")]
+    #[test]
+    fn test_kernel_normalization_2d() {
+        let (h, intervals) = (0.5, 20_000_u32);
+        let dr = 2.0 * h / f64::from(intervals);
+        let integral: f64 = (0..intervals)
+            .map(|k| {
+                let r = (f64::from(k) + 0.5) * dr;
+                2.0 * PI * r * cubic_spline(r, h).0 * dr
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1.0e-6, "integral = {integral}");
+    }
+}
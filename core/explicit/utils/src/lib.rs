@@ -1,7 +1,17 @@
+// `f64::from` widens `Real`, the same type in double precision
+#![cfg_attr(not(feature = "f32"), allow(clippy::useless_conversion))]
+
+pub mod boundary_velocity;
 pub mod bs_settings;
 pub mod cfl_condition;
+pub mod dimension;
 pub mod error;
+pub mod expression;
+pub mod grid_interpolation;
+pub mod kernel;
+pub mod materials;
 pub mod parameters;
 pub mod rw_checkpoint;
 pub mod sim_models;
+pub mod surface_mesh;
 pub mod write_csv;
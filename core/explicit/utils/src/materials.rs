@@ -0,0 +1,241 @@
+//! Material library: temperature-dependent density, viscosity and sound speed.
+//!
+//! Built-in entries: `water`, `air`, `oil`, `glycerol`, `honey`.
+//! User-defined materials are loaded from a JSON or TOML file:
+//!
+//! ```toml
+//! [[materials]]
+//! name = "brine"
+//! density = { Polynomial = [1025.0, -0.2] }
+//! viscosity = { Vogel = { a = 2.6e-5, b = 570.6, c = 140.0 } }
+//! sound_speed = { Constant = 1520.0 }
+//! ```
+use crate::error::{FailedReadFileSnafu, SimError};
+use snafu::ResultExt as _;
+
+/// Absolute zero [degC]
+const ZERO_CELSIUS: f64 = 273.15;
+
+/// Temperature correlation of a material property
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Correlation {
+    Constant(f64),
+    /// sum_k c_k T^k with T [degC]
+    Polynomial(Vec<f64>),
+    /// Vogel: a exp(b / (T - c)) with T [K]
+    Vogel {
+        a: f64,
+        b: f64,
+        c: f64,
+    },
+    /// Sutherland: mu0 (T / t0)^(3/2) (t0 + s) / (T + s) with T [K]
+    Sutherland {
+        mu0: f64,
+        t0: f64,
+        s: f64,
+    },
+    /// Ideal gas density: pressure / (gas_constant T) with T [K]
+    IdealGas {
+        pressure: f64,
+        gas_constant: f64,
+    },
+}
+
+impl Correlation {
+    /// Evaluate at the temperature [K]
+    pub fn eval(&self, temperature: f64) -> f64 {
+        match self {
+            Self::Constant(value) => *value,
+            Self::Polynomial(coefficients) => {
+                let t = temperature - ZERO_CELSIUS;
+                coefficients.iter().rev().fold(0.0, |acc, c| acc.mul_add(t, *c))
+            }
+            Self::Vogel { a, b, c } => a * (b / (temperature - c)).exp(),
+            Self::Sutherland { mu0, t0, s } => mu0 * (temperature / t0).powf(1.5) * (t0 + s) / (temperature + s),
+            Self::IdealGas { pressure, gas_constant } => pressure / (gas_constant * temperature),
+        }
+    }
+}
+
+/// Material properties
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Material {
+    pub name: String,
+    /// density [kg/m^3]
+    pub density: Correlation,
+    /// dynamic viscosity [Pa*s]
+    pub viscosity: Correlation,
+    /// sound speed [m/s]
+    pub sound_speed: Correlation,
+}
+
+impl Material {
+    pub fn water() -> Self {
+        Self {
+            name: "water".into(),
+            density: Correlation::Polynomial(vec![999.85, 0.0605, -0.00789, 3.7e-5]),
+            viscosity: Correlation::Vogel {
+                a: 2.414e-5,
+                b: 570.58,
+                c: 140.0,
+            },
+            sound_speed: Correlation::Polynomial(vec![1402.4, 5.04, -0.057]),
+        }
+    }
+
+    pub fn air() -> Self {
+        Self {
+            name: "air".into(),
+            density: Correlation::IdealGas {
+                pressure: 101_325.0,
+                gas_constant: 287.05,
+            },
+            viscosity: Correlation::Sutherland {
+                mu0: 1.716e-5,
+                t0: 273.15,
+                s: 110.4,
+            },
+            sound_speed: Correlation::Polynomial(vec![331.3, 0.6]),
+        }
+    }
+
+    /// Vegetable (olive) oil, approximate correlations for 10 ~ 60 degC
+    pub fn oil() -> Self {
+        Self {
+            name: "oil".into(),
+            density: Correlation::Polynomial(vec![925.0, -0.65]),
+            viscosity: Correlation::Vogel {
+                a: 8.39e-5,
+                b: 989.0,
+                c: 150.0,
+            },
+            sound_speed: Correlation::Polynomial(vec![1520.0, -3.5]),
+        }
+    }
+
+    /// Glycerol, approximate correlations for 10 ~ 60 degC
+    pub fn glycerol() -> Self {
+        Self {
+            name: "glycerol".into(),
+            density: Correlation::Polynomial(vec![1274.0, -0.65]),
+            viscosity: Correlation::Vogel {
+                a: 2.93e-6,
+                b: 1873.0,
+                c: 150.0,
+            },
+            sound_speed: Correlation::Polynomial(vec![1967.0, -2.2]),
+        }
+    }
+
+    /// Honey, approximate correlations for 10 ~ 60 degC
+    pub fn honey() -> Self {
+        Self {
+            name: "honey".into(),
+            density: Correlation::Polynomial(vec![1435.0, -0.75]),
+            viscosity: Correlation::Vogel {
+                a: 6.95e-8,
+                b: 2689.0,
+                c: 150.0,
+            },
+            sound_speed: Correlation::Constant(2000.0),
+        }
+    }
+}
+
+/// Named materials referenced by `Particle::material`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MaterialLibrary {
+    pub materials: Vec<Material>,
+}
+
+impl Default for MaterialLibrary {
+    fn default() -> Self {
+        Self {
+            materials: vec![
+                Material::water(),
+                Material::air(),
+                Material::oil(),
+                Material::glycerol(),
+                Material::honey(),
+            ],
+        }
+    }
+}
+
+impl MaterialLibrary {
+    /// Built-in materials extended (or overridden by name) with the materials of a JSON/TOML file.
+    /// # Errors
+    /// The file cannot be read or parsed
+    pub fn new(path: Option<&std::path::Path>) -> Result<Self, SimError> {
+        let mut library = Self::default();
+        if let Some(path) = path {
+            for material in Self::read(path)?.materials {
+                match library.materials.iter_mut().find(|m| m.name == material.name) {
+                    Some(m) => *m = material,
+                    None => library.materials.push(material),
+                }
+            }
+        }
+        Ok(library)
+    }
+
+    fn read(path: &std::path::Path) -> Result<Self, SimError> {
+        let text = std::fs::read_to_string(path).with_context(|_| FailedReadFileSnafu {
+            path: path.to_path_buf(),
+        })?;
+
+        let parsed = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|reason| SimError::FailedParseMaterials {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+
+    /// # Errors
+    /// Unknown material name
+    pub fn index_of(&self, name: &str) -> Result<usize, SimError> {
+        self.materials
+            .iter()
+            .position(|m| m.name == name)
+            .ok_or_else(|| SimError::UnknownMaterial { name: name.to_string() })
+    }
+
+    pub fn get(&self, index: usize) -> &Material {
+        &self.materials[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_library() {
+        let library = MaterialLibrary::default();
+        let water = library.get(library.index_of("water").unwrap());
+        let t20 = ZERO_CELSIUS + 20.0;
+        assert!((water.density.eval(t20) - 998.2).abs() < 0.5);
+        assert!((water.viscosity.eval(t20) - 1.0e-3).abs() < 0.05e-3);
+        assert!((water.sound_speed.eval(t20) - 1480.4).abs() < 1.0);
+
+        // User-defined materials extend the built-in ones
+        let test_file = std::env::temp_dir().join("test_materials.toml");
+        std::fs::write(
+            &test_file,
+            "[[materials]]\nname = \"brine\"\ndensity = { Polynomial = [1025.0, -0.2] }\n\
+             viscosity = { Constant = 1.1e-3 }\nsound_speed = { Constant = 1520.0 }\n",
+        )
+        .unwrap();
+        let library = MaterialLibrary::new(Some(&test_file)).unwrap();
+        let brine = library.get(library.index_of("brine").unwrap());
+        assert!((brine.density.eval(t20) - 1021.0).abs() < 1e-9);
+        assert!(library.index_of("water").is_ok());
+        assert!(library.index_of("mercury").is_err());
+
+        std::fs::remove_file(test_file).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
-use crate::parameters::{BC, LogReporterFn, particle_status::StopJudgeFn};
+use crate::parameters::{
+    BC, ForceReportConfig, FreeSurfaceConfig, GranularConfig, GridOutputConfig, IntegratorKind, LogReporterFn,
+    MaterialRegion, ModelKind, OpenBoundary, PorousZone, RefinementConfig, RigidBodyConfig, RotatingFrame, RunControl,
+    ScalarConfig, SolidConfig, SurfaceMeshConfig, TimeStepConfig, VariableSmoothing, VelocityFunction,
+    particle_status::StopJudgeFn,
+};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ModelScale {
@@ -23,6 +28,8 @@ pub struct Config {
     pub log_report: Option<LogReporterFn>,
     #[serde(skip)]
     pub stop_step: Option<StopJudgeFn>,
+    #[serde(skip)]
+    pub run_control: Option<RunControl>,
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
@@ -33,31 +40,86 @@ pub struct CheckpointConfig {
 
     // Model size
     pub model_scale: ModelScale,
+    /// Initial particle model
+    pub model: ModelKind,
+
+    // Materials
+    /// Material name of the model (see `materials::MaterialLibrary`)
+    pub material: String,
+    /// Materials of particle groups, overriding `material`
+    pub material_regions: Vec<MaterialRegion>,
+    /// User-defined material library (JSON/TOML)
+    pub materials_file: Option<std::path::PathBuf>,
+    /// Initial temperature [K]
+    pub temperature: f64,
 
     // Boundary Condition
     pub bc_pattern: BC,
     pub u_lid: f64,
+    /// Time/position dependence of `u_lid`
+    pub u_lid_fn: VelocityFunction,
+    pub open_boundary: OpenBoundary,
+
+    // Body force and rigid bodies
+    /// gravity [m/s^2]
+    pub gravity: [f64; 3],
+    /// Non-inertial (rotating) frame of the whole model
+    pub rotating_frame: Option<RotatingFrame>,
+    pub rigid_bodies: Vec<RigidBodyConfig>,
+
+    // Solid and granular materials
+    pub solids: Vec<SolidConfig>,
+    pub granular: Vec<GranularConfig>,
+
+    // Porous media
+    pub porous_zones: Vec<PorousZone>,
+
+    // Passive scalar transport
+    pub scalars: Vec<ScalarConfig>,
 
     // SPH parameters
     pub smooth_length: f64,
+    /// Smoothing length evolving with the density (`None`: h fixed per refinement level)
+    pub variable_h: Option<VariableSmoothing>,
+    /// Adaptive particle refinement (splitting and merging)
+    pub refinement: Option<RefinementConfig>,
+    /// Free-surface detection (surface flag and normal of the particles)
+    pub free_surface: Option<FreeSurfaceConfig>,
     pub cell_scale: f64,
     pub beta: f64,
     pub cs_rate: f64,
+    /// Sums in a fixed order: bitwise-identical results for any number of threads (slower)
+    pub deterministic: bool,
 
     // Resolution
     pub dx: Resolution,
 
     // Time stepping
+    /// Initial time step [s]
     pub dt: f64,
+    /// Adaptive time-step control
+    pub time_step: TimeStepConfig,
+    /// Time integration scheme
+    pub integrator: IntegratorKind,
     pub out_step: usize,
     pub max_step: usize,
 
     // Checkpoint file to restart
     pub restart_file: Option<std::path::PathBuf>,
     pub out_file: std::path::PathBuf,
+    /// Keep the state at the start of every step and write it with the failed state
+    /// next to `out_file` when the simulation blows up (opt-in: copies the whole state every step
+    /// and doubles its memory)
+    pub crash_checkpoint: bool,
 
     // Monitoring and log report
     pub monitor_particle: usize,
+    /// Lift/drag report on the immersed geometry
+    pub force_report: Option<ForceReportConfig>,
+    /// Surface mesh of the fluid written every output step
+    pub surface_mesh: Option<SurfaceMeshConfig>,
+    /// Particle fields interpolated onto a grid every output step
+    pub grid_output: Option<GridOutputConfig>,
 }
 
 impl Default for CheckpointConfig {
@@ -73,15 +135,43 @@ impl Default for CheckpointConfig {
                 width: 0.5,
                 height: 0.5,
             },
+            model: ModelKind::Box,
+
+            // materials
+            material: "water".into(),
+            material_regions: Vec::new(),
+            materials_file: None,
+            temperature: 273.15 + 20.0,
 
             // boundary condition
             bc_pattern: BC::CavityFlow,
             u_lid: 5.0,
+            u_lid_fn: VelocityFunction::Constant,
+            open_boundary: OpenBoundary::default(),
+
+            // body force and rigid bodies
+            gravity: [0.0; 3],
+            rotating_frame: None,
+            rigid_bodies: Vec::new(),
+
+            // solid and granular materials
+            solids: Vec::new(),
+            granular: Vec::new(),
+
+            // porous media
+            porous_zones: Vec::new(),
+
+            // passive scalars
+            scalars: Vec::new(),
 
             // SPH parameters
             smooth_length: 0.0324,
+            variable_h: None,
+            refinement: None,
+            free_surface: None,
             cell_scale: 2.0,
             beta: 0.3,
+            deterministic: false,
             cs_rate: 0.05,
 
             // resolution
@@ -93,13 +183,19 @@ impl Default for CheckpointConfig {
 
             // time control
             dt: 0.001,
+            time_step: TimeStepConfig::default(),
+            integrator: IntegratorKind::VelocityVerlet,
             out_step: 10,
             max_step: 10_000,
 
             // misc
             restart_file: None,
             out_file: std::path::PathBuf::from("./sim_checkpoint.bin"),
+            crash_checkpoint: false,
             monitor_particle: 10,
+            force_report: None,
+            surface_mesh: None,
+            grid_output: None,
         }
     }
 }
@@ -1,2 +1,20 @@
-// Dimension
+// Dimension (2D models with the `dim2` feature)
+#[cfg(not(feature = "dim2"))]
 pub const DIM: usize = 3;
+#[cfg(feature = "dim2")]
+pub const DIM: usize = 2;
+
+/// Scalar type of the particle state and the neighbor table (single precision with the `f32` feature)
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(feature = "f32")]
+pub type Real = f32;
+
+/// 3D axis (x: 0, y: 1, z: 2) of each model axis: 2D models lie in the x-y plane
+#[cfg(not(feature = "dim2"))]
+pub const AXES: [usize; DIM] = [0, 1, 2];
+#[cfg(feature = "dim2")]
+pub const AXES: [usize; DIM] = [0, 1];
+
+// Particle tag of the airfoil model
+pub const AIRFOIL_TAG: usize = 1;
@@ -0,0 +1,82 @@
+use crate::parameters::{AIRFOIL_TAG, Region};
+use nalgebra as na;
+
+/// Integration of the fluid loads over a group of boundary particles
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ForceReportConfig {
+    /// Particles forming the immersed geometry
+    pub region: Region,
+    /// reference area [m^2]
+    pub reference_area: f64,
+    /// reference (chord) length for the moment coefficient [m]
+    pub reference_length: f64,
+    /// reference velocity [m/s]
+    pub reference_velocity: f64,
+    /// reference density [kg/m^3]
+    pub reference_density: f64,
+    /// moment center [m]
+    pub moment_center: [f64; 3],
+    /// free-stream (drag) axis
+    pub drag_axis: usize,
+    /// lift axis (the moment is taken around the remaining axis)
+    pub lift_axis: usize,
+    /// time-series CSV
+    pub out_file: std::path::PathBuf,
+}
+
+impl Default for ForceReportConfig {
+    fn default() -> Self {
+        Self {
+            region: Region::Tag(AIRFOIL_TAG),
+            reference_area: 1.0,
+            reference_length: 1.0,
+            reference_velocity: 1.0,
+            reference_density: 1000.0,
+            moment_center: [0.25, 0.0, 0.0],
+            drag_axis: 0,
+            lift_axis: 1,
+            out_file: std::path::PathBuf::from("./results/body_force.csv"),
+        }
+    }
+}
+
+/// Fluid loads on the immersed geometry
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BodyLoads {
+    /// pressure force [N]
+    pub pressure: na::Vector3<f64>,
+    /// viscous force [N]
+    pub viscous: na::Vector3<f64>,
+    /// moment around the moment center [N*m]
+    pub moment: na::Vector3<f64>,
+    /// drag, lift and moment coefficients
+    pub cd: f64,
+    pub cl: f64,
+    pub cm: f64,
+}
+
+impl BodyLoads {
+    pub fn new(
+        pressure: na::Vector3<f64>,
+        viscous: na::Vector3<f64>,
+        moment: na::Vector3<f64>,
+        config: &ForceReportConfig,
+    ) -> Self {
+        let force = pressure + viscous;
+        let q = 0.5 * config.reference_density * config.reference_velocity.powi(2) * config.reference_area;
+        let moment_axis = 3 - config.drag_axis - config.lift_axis;
+
+        Self {
+            pressure,
+            viscous,
+            moment,
+            cd: force[config.drag_axis] / q,
+            cl: force[config.lift_axis] / q,
+            cm: moment[moment_axis] / (q * config.reference_length),
+        }
+    }
+
+    pub fn force(&self) -> na::Vector3<f64> {
+        self.pressure + self.viscous
+    }
+}
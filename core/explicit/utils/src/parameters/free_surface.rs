@@ -0,0 +1,23 @@
+/// Free-surface detection (Marrone et al. 2010)
+///
+/// The smallest eigenvalue of the renormalization matrix classifies the particles,
+/// and the undecided ones are checked for empty space along their normal.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FreeSurfaceConfig {
+    /// Particles with a smallest eigenvalue below this are on the surface
+    pub lambda_surface: f64,
+    /// Particles with a smallest eigenvalue above this (and div(r) above `div_r_threshold`) are interior
+    pub lambda_interior: f64,
+    /// Particles with a position divergence below this are surface candidates (DIM inside the fluid)
+    pub div_r_threshold: f64,
+}
+
+impl Default for FreeSurfaceConfig {
+    fn default() -> Self {
+        Self {
+            lambda_surface: 0.2,
+            lambda_interior: 0.75,
+            div_r_threshold: 0.8 * super::DIM as f64,
+        }
+    }
+}
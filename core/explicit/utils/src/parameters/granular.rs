@@ -0,0 +1,50 @@
+use crate::parameters::Region;
+
+/// Yield criterion of a granular material
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GranularModel {
+    /// Drucker–Prager yield stress fitted to Mohr–Coulomb (plane strain)
+    #[default]
+    DruckerPrager,
+    /// mu(I) rheology: mu = mu_s + (mu_2 - mu_s) / (I_0 / I + 1), mu_s = tan(friction_angle)
+    MuI {
+        mu_2: f64,
+        i_0: f64,
+        /// grain diameter [m]
+        grain_diameter: f64,
+    },
+}
+
+/// Erosion at the fluid–sediment interface
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ErosionConfig {
+    /// critical bed shear stress [Pa]
+    pub critical_shear_stress: f64,
+}
+
+/// Granular (sand, sediment) material made of the particles inside `region`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GranularConfig {
+    pub region: Region,
+    /// bulk density [kg/m^3]
+    pub density: f64,
+    /// internal friction angle [deg]
+    pub friction_angle: f64,
+    /// cohesion [Pa]
+    pub cohesion: f64,
+    /// dilatancy angle [deg]
+    pub dilatancy_angle: f64,
+    pub model: GranularModel,
+    /// viscosity of the unyielded (quasi-rigid) state [Pa*s]
+    pub max_viscosity: f64,
+    pub erosion: Option<ErosionConfig>,
+}
+
+impl GranularConfig {
+    /// Drucker–Prager coefficients (alpha, k): sqrt(J2) = 3 alpha p + k
+    pub fn drucker_prager(&self) -> (f64, f64) {
+        let tan_phi = self.friction_angle.to_radians().tan();
+        let denom = (12.0 * tan_phi).mul_add(tan_phi, 9.0).sqrt();
+        (tan_phi / denom, 3.0 * self.cohesion / denom)
+    }
+}
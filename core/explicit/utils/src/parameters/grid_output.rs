@@ -0,0 +1,67 @@
+/// Particle quantity interpolated onto the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GridField {
+    /// velocity [m/s]
+    Velocity,
+    /// pressure -tr(stress) / 3 [Pa]
+    Pressure,
+    /// density [kg/m^3]
+    Density,
+    /// vorticity [1/s]
+    Vorticity,
+}
+
+impl GridField {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Velocity => "velocity",
+            Self::Pressure => "pressure",
+            Self::Density => "density",
+            Self::Vorticity => "vorticity",
+        }
+    }
+
+    pub const fn components(self) -> usize {
+        match self {
+            Self::Velocity | Self::Vorticity => 3,
+            Self::Pressure | Self::Density => 1,
+        }
+    }
+}
+
+/// File format of the grid fields
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum GridFormat {
+    /// VTK XML image data (`.vti`)
+    #[default]
+    Vtk,
+    Csv,
+}
+
+/// Structured grid for the Eulerian output of the particle fields
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GridOutputConfig {
+    /// first grid node [m]
+    pub origin: [f64; 3],
+    /// node spacing [m]
+    pub spacing: [f64; 3],
+    /// node numbers
+    pub dims: [usize; 3],
+    pub fields: Vec<GridField>,
+    pub format: GridFormat,
+    /// Output directory of `grid_{step}.{ext}`
+    pub out_dir: std::path::PathBuf,
+}
+
+impl Default for GridOutputConfig {
+    fn default() -> Self {
+        Self {
+            origin: [0.0; 3],
+            spacing: [0.05; 3],
+            dims: [21; 3],
+            fields: vec![GridField::Velocity, GridField::Pressure],
+            format: GridFormat::Vtk,
+            out_dir: std::path::PathBuf::from("./results"),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+/// Time integration scheme of the evolved particle fields (x, v, rho, e)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum IntegratorKind {
+    /// Kick-drift-kick, symplectic, one force evaluation per step
+    #[default]
+    VelocityVerlet,
+    /// Drift-kick-drift, symplectic, one force evaluation per step
+    PositionVerlet,
+    /// Monaghan (1989) predictor-corrector at the half step
+    PredictorCorrector,
+    /// Explicit midpoint rule, two evaluations per step
+    RungeKutta2,
+    /// Classical fourth-order Runge-Kutta, four evaluations per step
+    RungeKutta4,
+}
@@ -0,0 +1,8 @@
+use crate::parameters::Region;
+
+/// Material (by name in the material library) of the particles inside `region`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MaterialRegion {
+    pub region: Region,
+    pub material: String,
+}
@@ -1,27 +1,60 @@
 mod boundary_condition;
 mod config;
 mod consts;
+mod force_report;
+mod free_surface;
+mod granular;
+mod grid_output;
+mod integrator;
+mod material_region;
+mod model;
+mod open_boundary;
 mod particle_neighbors;
 mod particle_status;
 mod particles;
+mod porous;
+mod refinement;
+mod region;
+mod rigid_body;
+mod rotating_frame;
+mod run_control;
+mod scalar;
+mod smoothing_length;
+mod solid;
+mod surface_mesh;
+mod time_step;
+mod velocity_function;
 
 pub use boundary_condition::BoundaryCondition;
 pub use config::{CheckpointConfig, Config, ModelScale, Resolution};
 pub use consts::*;
+pub use force_report::{BodyLoads, ForceReportConfig};
+pub use free_surface::FreeSurfaceConfig;
+pub use granular::{ErosionConfig, GranularConfig, GranularModel};
+pub use grid_output::{GridField, GridFormat, GridOutputConfig};
+pub use integrator::IntegratorKind;
+pub use material_region::MaterialRegion;
+pub use model::ModelKind;
+pub use open_boundary::{BufferZone, InflowProfile, InflowZone, OpenBoundary, OutflowZone};
 pub use particle_neighbors::NeighboringList;
 pub use particle_status::{LogReporterFn, ParticleLog, StopJudgeFn};
-pub use particles::Particle;
+pub use particles::{Particle, Phase};
+pub use porous::PorousZone;
+pub use refinement::RefinementConfig;
+pub use region::Region;
+pub use rigid_body::{BodyMotion, RigidBody, RigidBodyConfig};
+pub use rotating_frame::RotatingFrame;
+pub use run_control::{RunCommand, RunControl};
+pub use scalar::{ScalarConfig, ScalarRegion};
+pub use smoothing_length::VariableSmoothing;
+pub use solid::{SolidConfig, SolidModel};
+pub use surface_mesh::{MeshFormat, SurfaceMeshConfig};
+pub use time_step::{TimeStepConfig, TimeStepCriterion};
+pub use velocity_function::VelocityFunction;
 
 use nalgebra::{self as na};
 
-// Material information
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub enum Fluid {
-    Water,
-    Air,
-}
-
 /// type arias
-pub(crate) type Vector<const DIM: usize> = na::SVector<f64, DIM>;
-pub(crate) type Matrix<const DIM: usize> = na::Matrix<f64, na::Const<DIM>, na::Const<DIM>, na::ArrayStorage<f64, DIM, DIM>>;
+pub type Vector<const DIM: usize> = na::SVector<Real, DIM>;
+pub type Matrix<const DIM: usize> = na::Matrix<Real, na::Const<DIM>, na::Const<DIM>, na::ArrayStorage<Real, DIM, DIM>>;
 pub type BC = BoundaryCondition;
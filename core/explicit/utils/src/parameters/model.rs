@@ -0,0 +1,15 @@
+/// Initial particle model
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ModelKind {
+    /// Fluid block of `CheckpointConfig::model_scale` with the particle spacing `dx`
+    #[default]
+    Box,
+    /// Airfoil points (tagged `AIRFOIL_TAG`) in the surrounding air points, read from CSV files
+    /// with `x,y,z` columns; the volume of `model_scale` is shared by all particles
+    Airfoil {
+        airfoil_file: std::path::PathBuf,
+        air_space_file: std::path::PathBuf,
+        /// CSV of the model particle coordinates (`num,x,y,z`), e.g. `./results/model_particles.csv`
+        coordinates_file: Option<std::path::PathBuf>,
+    },
+}
@@ -0,0 +1,65 @@
+use crate::parameters::VelocityFunction;
+
+/// Buffer layer a particle belongs to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum BufferZone {
+    /// Regular fluid particle inside the domain
+    #[default]
+    Interior,
+    /// Particle of the inflow buffer (index into `OpenBoundary::inflow`)
+    Inflow(usize),
+    /// Particle of the outflow buffer (index into `OpenBoundary::outflow`)
+    Outflow(usize),
+}
+
+/// Velocity profile imposed over the inflow cross-section.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum InflowProfile {
+    #[default]
+    Uniform,
+    /// Fully developed channel profile spanning the model cross-section
+    Parabolic,
+}
+
+/// Inflow buffer: `[position - thickness, position]` along `axis`.
+/// The flow enters the domain in the positive `axis` direction.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InflowZone {
+    /// Flow axis (0: x, 1: y, 2: z)
+    pub axis: usize,
+    /// Buffer/domain interface [m]
+    pub position: f64,
+    /// Buffer thickness [m] (multiple of the particle spacing)
+    pub thickness: f64,
+    /// Mean inflow velocity [m/s]
+    pub velocity: f64,
+    /// Time/position dependence of `velocity`
+    pub velocity_fn: VelocityFunction,
+    pub profile: InflowProfile,
+    /// Inflow density [kg/m^3]
+    pub rho: f64,
+}
+
+/// Outflow buffer: `[position, position + thickness]` along `axis`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OutflowZone {
+    /// Flow axis (0: x, 1: y, 2: z)
+    pub axis: usize,
+    /// Domain/buffer interface [m]
+    pub position: f64,
+    /// Buffer thickness [m]
+    pub thickness: f64,
+}
+
+/// Open boundaries. Empty lists keep the particle number fixed.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct OpenBoundary {
+    pub inflow: Vec<InflowZone>,
+    pub outflow: Vec<OutflowZone>,
+}
+
+impl OpenBoundary {
+    pub const fn is_empty(&self) -> bool {
+        self.inflow.is_empty() && self.outflow.is_empty()
+    }
+}
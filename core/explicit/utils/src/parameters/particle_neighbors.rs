@@ -1,11 +1,14 @@
-use crate::parameters::Vector;
+use crate::{
+    dimension::to_vector3,
+    parameters::{DIM, Real, Vector},
+};
 
 // SPH Neighboring List
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct NeighboringList<const DIM: usize> {
     pub i: usize, // pair i
     pub j: usize, // pair j
-    pub w: f64,
+    pub w: Real,
     pub dwdr: Vector<DIM>,
 }
 
@@ -20,11 +23,10 @@ impl<const DIM: usize> Default for NeighboringList<DIM> {
     }
 }
 
-impl<const DIM: usize> NeighboringList<DIM> {
+impl NeighboringList<DIM> {
+    /// Kernel gradient (x, y, z)
     pub fn kernel_axis3(&self) -> (f64, f64, f64) {
-        let dwdr1 = self.dwdr[0];
-        let dwdr2 = self.dwdr[1];
-        let dwdr3 = self.dwdr[2];
-        (dwdr1, dwdr2, dwdr3)
+        let dwdr = to_vector3(&self.dwdr);
+        (dwdr[0], dwdr[1], dwdr[2])
     }
 }
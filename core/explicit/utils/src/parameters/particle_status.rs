@@ -1,4 +1,5 @@
-use crate::parameters::Vector;
+use crate::parameters::{BodyLoads, TimeStepCriterion};
+use nalgebra as na;
 use std::fmt::Debug;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -16,13 +17,26 @@ pub enum ParticleLog {
         time: f64,
 
         /// location vector [m]
-        x: Vector<3>,
+        x: na::Vector3<f64>,
         /// velocity [m/s]
-        v: Vector<3>,
+        v: na::Vector3<f64>,
         /// acceleration [m/s^2]
-        dvdt: Vector<3>,
+        dvdt: na::Vector3<f64>,
         // TODO: Energy and Temperature
     },
+    BodyForce {
+        step: usize,
+        time: f64,
+        loads: BodyLoads,
+    },
+    TimeStep {
+        step: usize,
+        time: f64,
+        /// time step [s]
+        dt: f64,
+        /// Criterion limiting the time step
+        criterion: TimeStepCriterion,
+    },
 }
 
 // type alias: Reporting particle status
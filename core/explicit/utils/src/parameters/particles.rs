@@ -1,56 +1,86 @@
-use crate::parameters::{Fluid, Matrix, Vector};
-use nalgebra::SimdComplexField;
+use crate::{
+    dimension::to_vector3,
+    materials::Material,
+    parameters::{BufferZone, DIM, Matrix, Real, Vector},
+};
+
+/// Role of a particle in the model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Phase {
+    #[default]
+    Fluid,
+    /// Boundary particle of a rigid body (index into `CheckpointConfig::rigid_bodies`)
+    Rigid(usize),
+    /// Elastic solid particle (index into `CheckpointConfig::solids`)
+    Solid(usize),
+    /// Granular particle (index into `CheckpointConfig::granular`)
+    Granular(usize),
+}
 
 // Particle information
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Particle<const DIM: usize> {
     // SPH parameters
-    pub pair: usize, // pair numbers per one particles
-    pub volume: f64, // [m^3]
+    pub pair: usize,  // pair numbers per one particles
+    pub volume: Real, // [m^3]
+    /// smoothing length [m] (set by the solver)
+    pub h: Real,
+    /// refinement level (0: base resolution, each level splits into 2^DIM daughters)
+    pub level: usize,
+    /// grad-h correction term Omega [-] (1: constant smoothing length)
+    pub omega: Real,
 
     // physical quantity for fluid
     /// initial density [kg/m^3]
-    pub rho0: f64,
+    pub rho0: Real,
     /// density [kg/m^3]
-    pub rho: f64,
+    pub rho: Real,
+    /// density rate [kg/m^3/s]
+    pub drhodt: Real,
     /// viscosity [Pa*s]
-    pub viscosity: f64,
+    pub viscosity: Real,
     /// sound velocity [m/s]
-    pub sound_v: f64,
+    pub sound_v: Real,
     /// location vector [m]
     pub x: Vector<DIM>,
     /// velocity [m/s]
     pub v: Vector<DIM>,
     /// Cauthy stress [Pa]
     pub stress: Matrix<DIM>,
+    /// Deviatoric stress of solids [Pa]
+    pub deviatoric: Matrix<DIM>,
     /// acceleration [m/s^2]
     pub dvdt: Vector<DIM>,
     /// total energy [J]
-    pub e: f64,
+    pub e: Real,
     /// power [J/s]
-    pub dedt: f64,
+    pub dedt: Real,
     /// Temperature [K]
-    pub temperature: f64,
-    /// Fluid type (Water, Air, etc.)
-    pub fluid: Fluid,
+    pub temperature: Real,
+    /// On the free surface
+    pub surface: bool,
+    /// Outward unit normal of the free surface (zero inside the fluid)
+    pub normal: Vector<DIM>,
+    /// Porosity of the surrounding medium [-] (1: free fluid)
+    pub porosity: Real,
+    /// Passive scalars (same order as `CheckpointConfig::scalars`)
+    pub scalars: Vec<Real>,
+    /// Material (index into the material library)
+    pub material: usize,
+    /// Open boundary buffer the particle belongs to
+    pub buffer: BufferZone,
+    /// Model tag (0: untagged)
+    pub tag: usize,
+    pub phase: Phase,
 }
 
 impl<const DIM: usize> Particle<DIM> {
-    pub fn new(fluid: Fluid) -> Self {
-        // Initial temperature and sound speed
-        let temperature: f64 = 273.15 + 20.0;
-        let sound_air = 0.6_f64.mul_add(temperature - 273.15, 331.3);
-        let sound_water = 0.057_f64.mul_add(
-            -(temperature - 273.15).simd_powf(2.0),
-            5.04_f64.mul_add(temperature - 273.15, 1402.4),
-        );
-
-        // Fluid properties
-        let (rho, viscosity, sound_v) = match fluid {
-            Fluid::Water => (1000.0, 0.001, sound_water),
-            // Fluid::Water => (1000.0, 1.0, sound_water),
-            Fluid::Air => (1.225, 0.0000181, sound_air),
-        };
+    /// New particle of the `material`-th library entry `properties` at `temperature` [K]
+    pub fn new(material: usize, properties: &Material, temperature: f64) -> Self {
+        // Material properties
+        let rho = properties.density.eval(temperature) as Real;
+        let viscosity = properties.viscosity.eval(temperature) as Real;
+        let sound_v = properties.sound_speed.eval(temperature) as Real;
 
         // initial value
         let rho0 = rho;
@@ -59,39 +89,75 @@ impl<const DIM: usize> Particle<DIM> {
         Self {
             pair: 0,
             volume: 0.5 * 0.5 * 0.5,
+            h: 0.0,
+            level: 0,
+            omega: 1.0,
             rho0,
             rho,
+            drhodt: 0.0,
             viscosity,
             sound_v,
             x: Vector::<DIM>::zeros(),
             v: Vector::<DIM>::zeros(),
             stress: Matrix::<DIM>::zeros(),
+            deviatoric: Matrix::<DIM>::zeros(),
             dvdt: Vector::<DIM>::zeros(),
             e: 0.0,
             dedt: 0.0,
-            temperature,
-            fluid,
+            temperature: temperature as Real,
+            surface: false,
+            normal: Vector::<DIM>::zeros(),
+            porosity: 1.0,
+            scalars: Vec::new(),
+            material,
+            buffer: BufferZone::Interior,
+            tag: 0,
+            phase: Phase::Fluid,
         }
     }
 
+    /// Switch to the `material`-th library entry `properties` at the current temperature.
+    pub fn set_material(&mut self, material: usize, properties: &Material) {
+        self.material = material;
+        let temperature = f64::from(self.temperature);
+        self.rho0 = properties.density.eval(temperature) as Real;
+        self.rho = self.rho0;
+        self.viscosity = properties.viscosity.eval(temperature) as Real;
+        self.sound_v = properties.sound_speed.eval(temperature) as Real;
+    }
+
+    /// Update the temperature [K] and the temperature-dependent properties.
+    /// The density is rescaled with the reference density.
+    pub fn set_temperature(&mut self, properties: &Material, temperature: f64) {
+        let rho0 = properties.density.eval(temperature) as Real;
+        self.rho *= rho0 / self.rho0;
+        self.rho0 = rho0;
+        self.viscosity = properties.viscosity.eval(temperature) as Real;
+        self.sound_v = properties.sound_speed.eval(temperature) as Real;
+        self.temperature = temperature as Real;
+    }
+
+    pub const fn is_rigid(&self) -> bool {
+        matches!(self.phase, Phase::Rigid(_))
+    }
+}
+
+impl Particle<DIM> {
+    /// Location (x, y, z) [m]
     pub fn axis(&self) -> (f64, f64, f64) {
-        let x = self.x[0];
-        let y = self.x[1];
-        let z = self.x[2];
-        (x, y, z)
+        let v = to_vector3(&self.x);
+        (v[0], v[1], v[2])
     }
 
+    /// Velocity (vx, vy, vz) [m/s]
     pub fn velocity(&self) -> (f64, f64, f64) {
-        let vx = self.v[0];
-        let vy = self.v[1];
-        let vz = self.v[2];
-        (vx, vy, vz)
+        let v = to_vector3(&self.v);
+        (v[0], v[1], v[2])
     }
 
+    /// Acceleration (ax, ay, az) [m/s^2]
     pub fn accel(&self) -> (f64, f64, f64) {
-        let ax = self.dvdt[0];
-        let ay = self.dvdt[1];
-        let az = self.dvdt[2];
-        (ax, ay, az)
+        let v = to_vector3(&self.dvdt);
+        (v[0], v[1], v[2])
     }
 }
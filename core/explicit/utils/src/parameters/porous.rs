@@ -0,0 +1,13 @@
+use crate::parameters::Region;
+
+/// Porous zone with Darcy–Forchheimer drag
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PorousZone {
+    pub region: Region,
+    /// porosity [-] (0 < porosity <= 1)
+    pub porosity: f64,
+    /// permeability [m^2]
+    pub permeability: f64,
+    /// Forchheimer inertial coefficient [-]
+    pub forchheimer: f64,
+}
@@ -0,0 +1,31 @@
+use crate::parameters::Region;
+
+/// Adaptive particle refinement
+///
+/// Fluid particles inside the refinement zone split into 8 daughters (half spacing and smoothing length)
+/// up to `max_level`, and groups of 8 neighboring daughters merge back once they leave it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RefinementConfig {
+    /// Regions refined unconditionally
+    pub regions: Vec<Region>,
+    /// Refine within this distance of the model box walls [m]
+    pub near_wall: Option<f64>,
+    /// Refine where the vorticity magnitude exceeds this value [1/s]
+    pub vorticity_threshold: Option<f64>,
+    /// Maximum refinement level
+    pub max_level: usize,
+    /// Steps between refinement updates
+    pub interval: usize,
+}
+
+impl Default for RefinementConfig {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            near_wall: None,
+            vorticity_threshold: None,
+            max_level: 1,
+            interval: 10,
+        }
+    }
+}
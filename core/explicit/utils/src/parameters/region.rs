@@ -0,0 +1,29 @@
+use crate::{
+    dimension::to_vector3,
+    parameters::{AXES, DIM, Particle},
+};
+
+/// Region of the model selecting a group of particles.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Region {
+    /// Axis-aligned box [m]
+    Box { min: [f64; 3], max: [f64; 3] },
+    /// Sphere [m]
+    Sphere { center: [f64; 3], radius: f64 },
+    /// Particles carrying the given tag (e.g. `AIRFOIL_TAG`)
+    Tag(usize),
+}
+
+impl Region {
+    pub fn contains(&self, particle: &Particle<DIM>) -> bool {
+        let x = to_vector3(&particle.x);
+        match self {
+            Self::Box { min, max } => AXES.iter().all(|&d| (min[d]..=max[d]).contains(&x[d])),
+            Self::Sphere { center, radius } => {
+                let r2: f64 = AXES.iter().map(|&d| (x[d] - center[d]).powi(2)).sum();
+                r2 <= radius * radius
+            }
+            Self::Tag(tag) => particle.tag == *tag,
+        }
+    }
+}
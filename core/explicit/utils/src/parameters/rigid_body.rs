@@ -0,0 +1,65 @@
+use crate::parameters::{Region, VelocityFunction};
+use nalgebra as na;
+
+/// Motion of a rigid body
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum BodyMotion {
+    /// Driven by the fluid force/torque (and gravity)
+    #[default]
+    Free,
+    /// Fixed in space
+    Fixed,
+    /// Prescribed velocity [m/s] and angular velocity [rad/s], scaled by `velocity_fn` (with `u = 1`)
+    Prescribed {
+        velocity: [f64; 3],
+        angular_velocity: [f64; 3],
+        velocity_fn: VelocityFunction,
+    },
+}
+
+/// Rigid body made of the boundary particles inside `region`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RigidBodyConfig {
+    pub region: Region,
+    /// Body density [kg/m^3]
+    pub density: f64,
+    pub motion: BodyMotion,
+    /// Apply `CheckpointConfig::gravity` to the body
+    pub gravity: bool,
+}
+
+/// Rigid body state
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RigidBody {
+    /// mass [kg]
+    pub mass: f64,
+    /// inertia tensor in the body frame [kg*m^2]
+    pub inertia: na::Matrix3<f64>,
+    /// center of mass [m]
+    pub center: na::Vector3<f64>,
+    /// velocity [m/s]
+    pub velocity: na::Vector3<f64>,
+    /// orientation (body -> world)
+    pub orientation: na::UnitQuaternion<f64>,
+    /// angular velocity [rad/s]
+    pub angular_velocity: na::Vector3<f64>,
+    /// total force [N]
+    pub force: na::Vector3<f64>,
+    /// total torque around the center of mass [N*m]
+    pub torque: na::Vector3<f64>,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self {
+            mass: 0.0,
+            inertia: na::Matrix3::zeros(),
+            center: na::Vector3::zeros(),
+            velocity: na::Vector3::zeros(),
+            orientation: na::UnitQuaternion::identity(),
+            angular_velocity: na::Vector3::zeros(),
+            force: na::Vector3::zeros(),
+            torque: na::Vector3::zeros(),
+        }
+    }
+}
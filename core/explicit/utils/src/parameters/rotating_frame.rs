@@ -0,0 +1,16 @@
+use crate::parameters::VelocityFunction;
+
+/// Non-inertial frame rotating about `axis` through `origin`.
+/// The angular velocity is `angular_velocity_fn` evaluated with `u = angular_velocity` at the origin.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RotatingFrame {
+    /// rotation axis (normalized internally)
+    pub axis: [f64; 3],
+    /// point on the rotation axis [m]
+    pub origin: [f64; 3],
+    /// angular velocity [rad/s]
+    pub angular_velocity: f64,
+    pub angular_velocity_fn: VelocityFunction,
+    /// Report positions and velocities in the inertial frame (checkpoints stay in the rotating frame)
+    pub output_inertial: bool,
+}
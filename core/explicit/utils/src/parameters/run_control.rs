@@ -0,0 +1,19 @@
+/// Command to a running simulation
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum RunCommand {
+    Pause,
+    Resume,
+    /// Advance one step while paused
+    Step,
+    /// Change `out_step`
+    SetOutStep(usize),
+    /// Change `u_lid` [m/s]
+    SetLidVelocity(f64),
+    /// Change `cs_rate`
+    SetSmoothingRate(f64),
+    /// Write a checkpoint of the current state
+    WriteCheckpoint,
+}
+
+// type alias: Receiving run commands, checked before every step
+pub type RunControl = std::sync::mpsc::Receiver<RunCommand>;
@@ -0,0 +1,22 @@
+use crate::parameters::Region;
+
+/// Initial value of a scalar inside a region
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScalarRegion {
+    pub region: Region,
+    pub value: f64,
+}
+
+/// Passive scalar (dye concentration, salinity, pollutant, ...) carried by the particles
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScalarConfig {
+    pub name: String,
+    /// diffusivity [m^2/s]
+    pub diffusivity: f64,
+    /// background initial value
+    pub initial: f64,
+    /// initial values per region (the last matching region wins)
+    pub regions: Vec<ScalarRegion>,
+    /// value carried by particles entering through inflow boundaries
+    pub inflow: f64,
+}
@@ -0,0 +1,21 @@
+/// Per-particle smoothing length following the density, h_i = h_0 (rho0_i / rho_i)^(1/3)
+/// with h_0 = `smooth_length` / 2^level (i.e. h proportional to (m / rho)^(1/3)).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VariableSmoothing {
+    /// Lower bound of h / h_0
+    pub min_ratio: f64,
+    /// Upper bound of h / h_0
+    pub max_ratio: f64,
+    /// Apply the grad-h correction terms to the kernel gradients
+    pub grad_h: bool,
+}
+
+impl Default for VariableSmoothing {
+    fn default() -> Self {
+        Self {
+            min_ratio: 0.5,
+            max_ratio: 2.0,
+            grad_h: true,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::parameters::Region;
+
+/// Constitutive model of a solid material
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SolidModel {
+    #[default]
+    LinearElastic,
+    /// von Mises plasticity with the yield stress [Pa]
+    ElasticPerfectlyPlastic { yield_stress: f64 },
+}
+
+/// Elastic solid made of the particles inside `region`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SolidConfig {
+    pub region: Region,
+    /// density [kg/m^3]
+    pub density: f64,
+    /// Young's modulus [Pa]
+    pub youngs_modulus: f64,
+    /// Poisson's ratio [-]
+    pub poisson_ratio: f64,
+    pub model: SolidModel,
+    /// Artificial stress coefficient against the tensile instability (0.2 ~ 0.3)
+    pub artificial_stress: f64,
+}
+
+impl SolidConfig {
+    /// shear modulus [Pa]
+    pub fn shear_modulus(&self) -> f64 {
+        self.youngs_modulus / (2.0 * (1.0 + self.poisson_ratio))
+    }
+
+    /// bulk modulus [Pa]
+    pub fn bulk_modulus(&self) -> f64 {
+        self.youngs_modulus / (3.0 * 2.0_f64.mul_add(-self.poisson_ratio, 1.0))
+    }
+
+    /// bulk sound speed [m/s]
+    pub fn sound_speed(&self) -> f64 {
+        (self.bulk_modulus() / self.density).sqrt()
+    }
+}
@@ -0,0 +1,41 @@
+/// File format of the surface meshes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MeshFormat {
+    #[default]
+    Obj,
+    Ply,
+    Stl,
+}
+
+impl MeshFormat {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Obj => "obj",
+            Self::Ply => "ply",
+            Self::Stl => "stl",
+        }
+    }
+}
+
+/// Surface reconstruction of the fluid particles (color field + marching tetrahedra)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SurfaceMeshConfig {
+    /// Grid spacing of the color field [m]
+    pub cell_size: f64,
+    /// Iso value of the color field (1 inside the fluid, 0 outside)
+    pub iso_level: f64,
+    pub format: MeshFormat,
+    /// Output directory of `surface_{step}.{ext}`
+    pub out_dir: std::path::PathBuf,
+}
+
+impl Default for SurfaceMeshConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: 0.01,
+            iso_level: 0.5,
+            format: MeshFormat::Obj,
+            out_dir: std::path::PathBuf::from("./results"),
+        }
+    }
+}
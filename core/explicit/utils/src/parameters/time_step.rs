@@ -0,0 +1,65 @@
+/// Stability criterion limiting the time step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TimeStepCriterion {
+    /// dt = C_cfl h / (c + |v|)
+    #[default]
+    Acoustic,
+    /// dt = C_visc h^2 / nu
+    Viscous,
+    /// dt = C_force sqrt(h / |a|)
+    Force,
+    /// dt = C_thermal h^2 / alpha
+    Thermal,
+    /// Growth limit from the previous time step
+    Growth,
+    /// Upper bound `max_dt`
+    Maximum,
+}
+
+/// Adaptive time-step control: the smallest of the stability criteria over all particles
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TimeStepConfig {
+    /// Courant number of the acoustic criterion
+    pub cfl: f64,
+    /// Diffusion number of the viscous criterion
+    pub viscous: f64,
+    /// Diffusion number of the thermal criterion
+    pub thermal: f64,
+    /// Safety factor of the body-force criterion
+    pub force: f64,
+    /// Thermal diffusivity [m^2/s] (`None`: no thermal criterion)
+    pub thermal_diffusivity: Option<f64>,
+    /// Lower bound [s]: the simulation stops below it
+    pub min_dt: f64,
+    /// Upper bound [s] (`None`: the initial `dt`)
+    pub max_dt: Option<f64>,
+    /// Largest ratio of consecutive time steps
+    pub max_growth: f64,
+    /// Number of individual time-step levels dt, dt/2, ..., dt/2^(levels - 1) (1: global time step).
+    /// The particles are binned by their own stability limit and synchronized every dt
+    /// (kick-drift-kick scheme for more than one level).
+    pub levels: usize,
+}
+
+impl TimeStepConfig {
+    /// Substeps of the finest level per time step
+    pub const fn substeps(&self) -> usize {
+        1 << (self.levels.saturating_sub(1))
+    }
+}
+
+impl Default for TimeStepConfig {
+    fn default() -> Self {
+        Self {
+            cfl: 0.3,
+            viscous: 0.125,
+            thermal: 0.125,
+            force: 0.25,
+            thermal_diffusivity: None,
+            min_dt: 1.0e-9,
+            max_dt: None,
+            max_growth: 1.1,
+            levels: 1,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+/// Time/position dependence of a boundary velocity.
+/// The configured velocity (e.g. `u_lid`) is available as `u`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum VelocityFunction {
+    /// `u`
+    #[default]
+    Constant,
+    /// `u * min(t / duration, 1)`
+    Ramp { duration: f64 },
+    /// `u * sin(2 * pi * frequency * t + phase)`
+    Sine { frequency: f64, phase: f64 },
+    /// Time series read from a CSV file with `t,u` columns (linear interpolation)
+    Table { path: std::path::PathBuf },
+    /// Expression of `t, x, y, z, u`, e.g. `5.0*min(t/0.1,1)`
+    Expression(String),
+}
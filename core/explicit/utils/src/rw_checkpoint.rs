@@ -1,5 +1,5 @@
 use crate::error::{FailedReadFileSnafu, FailedWriteFileSnafu, PostcardSnafu, SimError};
-use crate::parameters::{CheckpointConfig, DIM, NeighboringList, Particle};
+use crate::parameters::{CheckpointConfig, DIM, NeighboringList, Particle, RigidBody};
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt as _;
 use std::borrow::Cow;
@@ -12,6 +12,7 @@ pub struct State<'a, const D: usize> {
     pub particles: Cow<'a, [Particle<D>]>,
     #[serde(bound(deserialize = "Cow<'a, [NeighboringList<D>]>: serde::Deserialize<'de>"))]
     pub neighbors: Cow<'a, [NeighboringList<D>]>,
+    pub bodies: Cow<'a, [RigidBody]>,
     pub step: usize,
     pub time: f64,
 }
@@ -65,6 +66,7 @@ pub fn write_sim_checkpoint(
     config: &CheckpointConfig,
     particles: &[Particle<DIM>],
     neighbors: &[NeighboringList<DIM>],
+    bodies: &[RigidBody],
     step: usize,
     time: f64,
 ) -> Result<(), SimError> {
@@ -72,6 +74,7 @@ pub fn write_sim_checkpoint(
         checkpoint_config: Cow::Borrowed(config),
         particles: Cow::Borrowed(particles),
         neighbors: Cow::Borrowed(neighbors),
+        bodies: Cow::Borrowed(bodies),
         step,
         time,
     };
@@ -83,7 +86,8 @@ pub fn write_sim_checkpoint(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parameters::{Config, DIM, Fluid, Particle};
+    use crate::materials::Material;
+    use crate::parameters::{Config, DIM, Particle};
 
     #[test]
     fn test_checkpoint_roundtrip() {
@@ -92,8 +96,9 @@ mod tests {
 
         let config = Config::default();
         let n = 3;
-        let water = Fluid::Water;
-        let particles: Vec<Particle<DIM>> = (0..n).map(|_| Particle::new(water)).collect();
+        let water = Material::water();
+        let temperature = config.checkpoint_config.temperature;
+        let particles: Vec<Particle<DIM>> = (0..n).map(|_| Particle::new(0, &water, temperature)).collect();
         let neighbors: Vec<NeighboringList<DIM>> = (0..n).map(|_| NeighboringList::default()).collect();
 
         let step = 10;
@@ -102,6 +107,7 @@ mod tests {
             checkpoint_config: Cow::Borrowed(&config.checkpoint_config),
             particles: Cow::Borrowed(&particles[0..n]),
             neighbors: Cow::Borrowed(&neighbors[0..n]),
+            bodies: Cow::Borrowed(&[]),
             step,
             time,
         };
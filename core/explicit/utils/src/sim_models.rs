@@ -1,8 +1,10 @@
 use crate::{
+    dimension::from_vector3,
     error::{FailedFeatureReadFileSnafu, FailedWriteFileSnafu, SimError},
-    parameters::{DIM, ModelScale, Particle, Resolution},
+    parameters::{AIRFOIL_TAG, AXES, DIM, ModelKind, ModelScale, Particle, Real, Resolution},
 };
 use csv::ReaderBuilder;
+use nalgebra as na;
 use serde::Deserialize;
 use snafu::ResultExt as _;
 
@@ -63,11 +65,12 @@ fn read_airfoil_data(file_path: &std::path::Path) -> Result<Vec<Airfoil>, SimErr
     Ok(airfoil_data)
 }
 
-// Generate particles in a 3D grid
+// Generate particles of 3D point data (projected onto the plane of 2D models)
 fn sim_model(
-    particles: &mut [Particle<3>],
+    particles: &mut [Particle<DIM>],
     airfoil_data: &[Airfoil],
     air_space_data: &[AirSpace],
+    model_scale: &ModelScale,
 ) -> Result<usize, SimError> {
     let n = airfoil_data.len() + air_space_data.len();
 
@@ -79,34 +82,32 @@ fn sim_model(
     }
 
     for (i, airfoil) in airfoil_data.iter().enumerate() {
-        if i < airfoil_data.len() - 1 {
-            particles[i].x[0] = airfoil.x;
-            particles[i].x[1] = airfoil.y;
-            particles[i].x[2] = airfoil.z;
-        }
+        particles[i].x = from_vector3(&na::Vector3::new(airfoil.x, airfoil.y, airfoil.z));
+        particles[i].tag = AIRFOIL_TAG;
     }
 
     for (i, air_space) in air_space_data.iter().enumerate() {
         let i2 = i + airfoil_data.len();
 
         if i2 < n {
-            particles[i2].x[0] = air_space.x;
-            particles[i2].x[1] = air_space.y;
-            particles[i2].x[2] = air_space.z;
+            particles[i2].x = from_vector3(&na::Vector3::new(air_space.x, air_space.y, air_space.z));
         }
     }
 
-    // Assign volume evenly across all particles
+    // Assign volume evenly across all particles (area per unit depth in 2D)
+    let volume: f64 = AXES
+        .iter()
+        .map(|&axis| [model_scale.length, model_scale.width, model_scale.height][axis])
+        .product();
     for particle in particles.iter_mut().take(n) {
-        particle.volume /= n as f64;
+        particle.volume = (volume / n as f64) as Real;
     }
 
     Ok(n)
 }
 
 // Function to write particle coordinates to a CSV file
-fn write_coordinates_to_csv(particles: &[Particle<3>]) -> Result<(), SimError> {
-    let filename = std::path::Path::new("./results/model_particles.csv");
+fn write_coordinates_to_csv(filename: &std::path::Path, particles: &[Particle<DIM>]) -> Result<(), SimError> {
     let mut csv = String::new();
 
     // Write CSV header
@@ -135,37 +136,36 @@ fn make_box_model(
     // Particle counter, starts from 0
     let mut n = 0;
 
-    let (nx, ny, nz) = (
-        (model_scale.length / resolution.dx) as usize,
-        (model_scale.width / resolution.dy) as usize,
-        (model_scale.height / resolution.dz) as usize,
-    );
-
-    for i in 0..=nx {
-        for j in 0..=ny {
-            for k in 0..=nz {
-                // Check if we exceed the maximum number of particles
-                if n >= particles.len() {
-                    return Err(SimError::ExceededMaxNumber {
-                        n,
-                        max_n: particles.len(),
-                    });
-                }
-
-                // Set particle location
-                particles[n].x[0] = i as f64 * resolution.dx; // x
-                particles[n].x[1] = j as f64 * resolution.dy; // y
-                particles[n].x[2] = k as f64 * resolution.dz; // z
-
-                n += 1; // Increment particle counter
-            }
+    // Size and spacing along the model axes
+    let size = AXES.map(|axis| [model_scale.length, model_scale.width, model_scale.height][axis]);
+    let spacing = AXES.map(|axis| [resolution.dx, resolution.dy, resolution.dz][axis]);
+    let counts: [usize; DIM] = std::array::from_fn(|d| (size[d] / spacing[d]) as usize + 1);
+    let total: usize = counts.iter().product();
+
+    // The last axis runs fastest
+    for index in 0..total {
+        // Check if we exceed the maximum number of particles
+        if n >= particles.len() {
+            return Err(SimError::ExceededMaxNumber {
+                n,
+                max_n: particles.len(),
+            });
         }
+
+        // Set particle location
+        let mut rest = index;
+        for d in (0..DIM).rev() {
+            particles[n].x[d] = ((rest % counts[d]) as f64 * spacing[d]) as Real;
+            rest /= counts[d];
+        }
+
+        n += 1; // Increment particle counter
     }
 
-    // Particle volume
-    let volume = model_scale.length * model_scale.width * model_scale.height;
+    // Particle volume (area per unit depth in 2D)
+    let volume: f64 = size.iter().product();
     for particle in particles.iter_mut().take(n) {
-        particle.volume = volume / n as f64;
+        particle.volume = (volume / n as f64) as Real;
     }
 
     // Debug
@@ -174,32 +174,32 @@ fn make_box_model(
     Ok(n)
 }
 
+/// Making simulation models: the number of particles
 /// # Errors
-// Making simulation models
+/// MAX Particles < N, unreadable airfoil files, unwritable coordinates file
 pub fn make_model(
-    model: &str,
+    model: &ModelKind,
     particles: &mut [Particle<DIM>],
     model_scale: &ModelScale,
     resolution: &Resolution,
 ) -> Result<usize, SimError> {
-    if model == "csv" {
-        // Read air_space.csv file
-        let csv_path = std::path::Path::new("src/models/air_space.csv");
-        let air_space_data = read_air_space(csv_path)?;
-
-        // Read airfoil data file
-        let csv_path = std::path::Path::new("src/models/naca_2412_3d_airfoil.csv");
-        let airfoil_data = read_airfoil_data(csv_path)?;
-
-        // Setting model
-        let n = sim_model(particles, &airfoil_data, &air_space_data)?;
-
-        // Write particle coordinates to CSV
-        write_coordinates_to_csv(&particles[0..n])?;
-
-        Ok(n)
-    } else {
-        // Default: Box
-        make_box_model(particles, model_scale, resolution)
+    match model {
+        ModelKind::Box => make_box_model(particles, model_scale, resolution),
+        ModelKind::Airfoil {
+            airfoil_file,
+            air_space_file,
+            coordinates_file,
+        } => {
+            // Read air space and airfoil data files
+            let air_space_data = read_air_space(air_space_file)?;
+            let airfoil_data = read_airfoil_data(airfoil_file)?;
+
+            // Setting model
+            let n = sim_model(particles, &airfoil_data, &air_space_data, model_scale)?;
+            if let Some(file) = coordinates_file {
+                write_coordinates_to_csv(file, &particles[0..n])?;
+            }
+            Ok(n)
+        }
     }
 }
@@ -0,0 +1,311 @@
+use crate::{
+    dimension::to_vector3,
+    error::{FailedWriteFileSnafu, SimError},
+    kernel::cubic_spline,
+    parameters::{DIM, MeshFormat, Particle, Phase, SurfaceMeshConfig},
+};
+use nalgebra as na;
+use snafu::ResultExt as _;
+use std::{collections::HashMap, fmt::Write as _};
+
+/// Decomposition of a grid cell into 6 tetrahedra around the diagonal 0-7
+/// (corner bits: x = 1, y = 2, z = 4)
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+/// Triangle mesh with outward oriented triangles
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TriangleMesh {
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+/// Color field c(x) = sum_j V_j W(x - x_j, h_j) sampled on a regular grid
+struct ColorField {
+    origin: na::Vector3<f64>,
+    cell_size: f64,
+    dims: [usize; 3],
+    values: Vec<f64>,
+}
+
+impl ColorField {
+    fn new(particles: &[&Particle<DIM>], cell_size: f64) -> Self {
+        let position = |p: &Particle<DIM>| to_vector3(&p.x);
+        let h_max = particles.iter().map(|p| f64::from(p.h)).fold(0.0, f64::max);
+        let margin = 2.0_f64.mul_add(h_max, cell_size);
+
+        let mut min = na::Vector3::repeat(f64::INFINITY);
+        let mut max = na::Vector3::repeat(f64::NEG_INFINITY);
+        for p in particles {
+            min = min.inf(&position(p));
+            max = max.sup(&position(p));
+        }
+        let origin = min.add_scalar(-margin);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let dims = [0, 1, 2].map(|d| (2.0_f64.mul_add(margin, max[d] - min[d]) / cell_size).ceil() as usize + 1);
+
+        let mut field = Self {
+            origin,
+            cell_size,
+            dims,
+            values: vec![0.0; dims[0] * dims[1] * dims[2]],
+        };
+
+        // Scatter each particle onto the nodes inside its support
+        for p in particles {
+            let x = position(p);
+            let (h, volume) = (f64::from(p.h), f64::from(p.volume));
+            let support = 2.0 * h;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let range = |d: usize| {
+                let lo = ((x[d] - support - origin[d]) / cell_size).floor().max(0.0) as usize;
+                let hi = (((x[d] + support - origin[d]) / cell_size).ceil() as usize).min(dims[d] - 1);
+                lo..=hi
+            };
+            for k in range(2) {
+                for j in range(1) {
+                    for i in range(0) {
+                        let d = (field.node(i, j, k) - x).norm();
+                        let index = field.index(i, j, k);
+                        field.values[index] += volume * cubic_spline(d, h).0;
+                    }
+                }
+            }
+        }
+        field
+    }
+
+    const fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        (k * self.dims[1] + j) * self.dims[0] + i
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn node(&self, i: usize, j: usize, k: usize) -> na::Vector3<f64> {
+        self.origin + self.cell_size * na::Vector3::new(i as f64, j as f64, k as f64)
+    }
+}
+
+/// Builder of the mesh sharing the vertices on the grid edges
+#[derive(Default)]
+struct MeshBuilder {
+    mesh: TriangleMesh,
+    edge_vertices: HashMap<(usize, usize), usize>,
+}
+
+impl MeshBuilder {
+    /// Vertex on the grid edge a-b where the field crosses `iso`
+    fn vertex(&mut self, field: &ColorField, a: (usize, na::Vector3<f64>), b: (usize, na::Vector3<f64>), iso: f64) -> usize {
+        let key = (a.0.min(b.0), a.0.max(b.0));
+        if let Some(&v) = self.edge_vertices.get(&key) {
+            return v;
+        }
+        let (ca, cb) = (field.values[a.0], field.values[b.0]);
+        let t = if (cb - ca).abs() > f64::EPSILON {
+            ((iso - ca) / (cb - ca)).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        let x = a.1 + t * (b.1 - a.1);
+
+        let v = self.mesh.vertices.len();
+        self.mesh.vertices.push([x[0], x[1], x[2]]);
+        self.edge_vertices.insert(key, v);
+        v
+    }
+
+    /// Add a triangle with its normal pointing along `outward`
+    fn triangle(&mut self, mut tri: [usize; 3], outward: &na::Vector3<f64>) {
+        let p = tri.map(|v| na::Vector3::from(self.mesh.vertices[v]));
+        if (p[1] - p[0]).cross(&(p[2] - p[0])).dot(outward) < 0.0 {
+            tri.swap(1, 2);
+        }
+        self.mesh.triangles.push(tri);
+    }
+
+    /// Marching tetrahedra on one tetrahedron of (node index, position) corners
+    fn tetrahedron(&mut self, field: &ColorField, corners: [(usize, na::Vector3<f64>); 4], iso: f64) {
+        let (inside, outside): (Vec<_>, Vec<_>) = corners.into_iter().partition(|c| field.values[c.0] > iso);
+        if inside.is_empty() || outside.is_empty() {
+            return;
+        }
+        let centroid = |cs: &[(usize, na::Vector3<f64>)]| {
+            #[allow(clippy::cast_precision_loss)]
+            let count = cs.len() as f64;
+            cs.iter().map(|c| c.1).sum::<na::Vector3<f64>>() / count
+        };
+        let outward = centroid(&outside) - centroid(&inside);
+
+        match (inside.as_slice(), outside.as_slice()) {
+            // One corner separated from the three others
+            ([a], [b, c, d]) | ([b, c, d], [a]) => {
+                let tri = [
+                    self.vertex(field, *a, *b, iso),
+                    self.vertex(field, *a, *c, iso),
+                    self.vertex(field, *a, *d, iso),
+                ];
+                self.triangle(tri, &outward);
+            }
+            // Quad between two pairs of corners
+            ([a, b], [c, d]) => {
+                let ac = self.vertex(field, *a, *c, iso);
+                let ad = self.vertex(field, *a, *d, iso);
+                let bd = self.vertex(field, *b, *d, iso);
+                let bc = self.vertex(field, *b, *c, iso);
+                self.triangle([ac, ad, bd], &outward);
+                self.triangle([ac, bd, bc], &outward);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TriangleMesh {
+    /// Iso-surface of the color field of the fluid particles (marching tetrahedra)
+    pub fn reconstruct(particles: &[Particle<DIM>], config: &SurfaceMeshConfig) -> Self {
+        let fluid: Vec<&Particle<DIM>> = particles.iter().filter(|p| p.phase == Phase::Fluid).collect();
+        if fluid.is_empty() {
+            return Self::default();
+        }
+        let field = ColorField::new(&fluid, config.cell_size);
+        let [nx, ny, nz] = field.dims;
+
+        let mut builder = MeshBuilder::default();
+        for k in 0..nz - 1 {
+            for j in 0..ny - 1 {
+                for i in 0..nx - 1 {
+                    let corner = |c: usize| {
+                        let (ci, cj, ck) = (i + (c & 1), j + ((c >> 1) & 1), k + ((c >> 2) & 1));
+                        (field.index(ci, cj, ck), field.node(ci, cj, ck))
+                    };
+                    for tet in TETRAHEDRA {
+                        builder.tetrahedron(&field, tet.map(corner), config.iso_level);
+                    }
+                }
+            }
+        }
+        builder.mesh
+    }
+
+    /// Enclosed volume [m^3] (divergence theorem, closed mesh)
+    pub fn volume(&self) -> f64 {
+        self.triangles
+            .iter()
+            .map(|tri| {
+                let [a, b, c] = tri.map(|v| na::Vector3::from(self.vertices[v]));
+                a.dot(&b.cross(&c)) / 6.0
+            })
+            .sum()
+    }
+
+    fn to_obj(&self) -> String {
+        let mut out = String::new();
+        for [x, y, z] in &self.vertices {
+            let _ = writeln!(out, "v {x} {y} {z}");
+        }
+        for [a, b, c] in &self.triangles {
+            let _ = writeln!(out, "f {} {} {}", a + 1, b + 1, c + 1);
+        }
+        out
+    }
+
+    fn to_ply(&self) -> String {
+        let mut out = format!(
+            "ply\nformat ascii 1.0\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+             element face {}\nproperty list uchar int vertex_indices\nend_header\n",
+            self.vertices.len(),
+            self.triangles.len()
+        );
+        for [x, y, z] in &self.vertices {
+            let _ = writeln!(out, "{x} {y} {z}");
+        }
+        for [a, b, c] in &self.triangles {
+            let _ = writeln!(out, "3 {a} {b} {c}");
+        }
+        out
+    }
+
+    fn to_stl(&self) -> String {
+        let mut out = String::from("solid surface\n");
+        for tri in &self.triangles {
+            let [a, b, c] = tri.map(|v| na::Vector3::from(self.vertices[v]));
+            let n = (b - a)
+                .cross(&(c - a))
+                .try_normalize(f64::EPSILON)
+                .unwrap_or_else(na::Vector3::zeros);
+            let _ = writeln!(out, "facet normal {} {} {}\nouter loop", n[0], n[1], n[2]);
+            for p in [a, b, c] {
+                let _ = writeln!(out, "vertex {} {} {}", p[0], p[1], p[2]);
+            }
+            out.push_str("endloop\nendfacet\n");
+        }
+        out.push_str("endsolid surface\n");
+        out
+    }
+
+    /// # Errors
+    /// Write the mesh in the given format.
+    pub fn write(&self, path: &std::path::Path, format: MeshFormat) -> Result<(), SimError> {
+        let content = match format {
+            MeshFormat::Obj => self.to_obj(),
+            MeshFormat::Ply => self.to_ply(),
+            MeshFormat::Stl => self.to_stl(),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|_| FailedWriteFileSnafu {
+                path: parent.to_path_buf(),
+            })?;
+        }
+        std::fs::write(path, content).with_context(|_| FailedWriteFileSnafu {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// # Errors
+/// Reconstruct the fluid surface and write `surface_{step}.{ext}`.
+pub fn write_surface_mesh(step: usize, particles: &[Particle<DIM>], config: &SurfaceMeshConfig) -> Result<(), SimError> {
+    let path = config.out_dir.join(format!("surface_{step}.{}", config.format.extension()));
+    TriangleMesh::reconstruct(particles, config).write(&path, config.format)
+}
+
+#[cfg(all(test, not(feature = "dim2")))]
+mod tests {
+    use super::*;
+    use crate::{materials::Material, parameters::Real};
+
+    /// A block of particles gives a closed surface enclosing about its volume
+    #[test]
+    fn test_block_surface() {
+        let dx = 0.1;
+        let template = Particle::<DIM>::new(0, &Material::water(), 293.15);
+        let mut particles = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                for k in 0..10 {
+                    let mut p = template.clone();
+                    p.x = (na::Vector3::new(f64::from(i), f64::from(j), f64::from(k)) * dx).cast();
+                    p.volume = (dx * dx * dx) as Real;
+                    p.h = (1.3 * dx) as Real;
+                    particles.push(p);
+                }
+            }
+        }
+
+        let config = SurfaceMeshConfig {
+            cell_size: 0.05,
+            ..Default::default()
+        };
+        let mesh = TriangleMesh::reconstruct(&particles, &config);
+        assert!(!mesh.triangles.is_empty());
+
+        // 1000 particles of 1e-3 m^3
+        let volume = mesh.volume();
+        assert!((volume - 1.0).abs() < 0.15, "volume = {volume}");
+    }
+}
@@ -1,10 +1,10 @@
 use crate::{
+    dimension::to_vector3,
     error::{FailedWriteFileSnafu, SimError},
-    parameters::{LogReporterFn, ParticleLog},
+    parameters::{BodyLoads, LogReporterFn, ParticleLog, ScalarConfig},
 };
 
 use super::parameters::{DIM, Particle};
-use nalgebra as na;
 use snafu::ResultExt as _;
 
 /// # Errors
@@ -40,15 +40,92 @@ pub fn write_velocity_to_csv(step: usize, particles: &[Particle<DIM>]) -> Result
 }
 
 /// # Errors
-pub fn display_result(monitor_particle: usize, status: &LogReporterFn, step: usize, time: f64, particles: &[Particle<DIM>]) {
-    let x: [f64; DIM] = particles[monitor_particle].axis().into();
-    let v: [f64; DIM] = particles[monitor_particle].velocity().into();
-    let dvdt: [f64; DIM] = particles[monitor_particle].accel().into();
+pub fn write_scalars_to_csv(step: usize, particles: &[Particle<DIM>], scalars: &[ScalarConfig]) -> Result<(), SimError> {
+    let filename = std::path::PathBuf::from(format!("./results/scalars_{}.csv", step));
+    let mut csv = String::new();
+
+    // CSV header
+    csv.push_str("i,x,y,z");
+    for scalar in scalars {
+        csv.push(',');
+        csv.push_str(&scalar.name);
+    }
+    csv.push('\n');
+
+    for (i, particle) in particles.iter().enumerate() {
+        let (x, y, z) = particle.axis();
+        csv.push_str(&format!("{i},{x:.3},{y:.3},{z:.3}"));
+        for value in &particle.scalars {
+            csv.push_str(&format!(",{value:.6e}"));
+        }
+        csv.push('\n');
+    }
+
+    if let Some(parent) = filename.parent() {
+        std::fs::create_dir_all(parent).with_context(|_| FailedWriteFileSnafu {
+            path: parent.to_path_buf(),
+        })?;
+    }
+    std::fs::write(&filename, &csv).with_context(|_| FailedWriteFileSnafu { path: filename })?;
+    Ok(())
+}
 
-    let x = na::Vector3::from(x);
-    let v = na::Vector3::from(v);
-    let dvdt = na::Vector3::from(dvdt);
+/// # Errors
+pub fn display_result(monitor_particle: usize, status: &LogReporterFn, step: usize, time: f64, particles: &[Particle<DIM>]) {
+    let p = &particles[monitor_particle];
+    let x = to_vector3(&p.x);
+    let v = to_vector3(&p.v);
+    let dvdt = to_vector3(&p.dvdt);
 
     #[rustfmt::skip]
     status(ParticleLog::Info3 { step, time, monitor_particle, x, v, dvdt });
 }
+
+/// # Errors
+/// Create the body force time series (header only).
+pub fn write_body_force_header(path: &std::path::Path) -> Result<(), SimError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|_| FailedWriteFileSnafu {
+            path: parent.to_path_buf(),
+        })?;
+    }
+
+    let header = "step,time,fx,fy,fz,fpx,fpy,fpz,fvx,fvy,fvz,mx,my,mz,cd,cl,cm\n";
+    std::fs::write(path, header).with_context(|_| FailedWriteFileSnafu {
+        path: path.to_path_buf(),
+    })?;
+    Ok(())
+}
+
+/// # Errors
+/// Append one record to the body force time series.
+pub fn append_body_force_to_csv(path: &std::path::Path, step: usize, time: f64, loads: &BodyLoads) -> Result<(), SimError> {
+    use std::io::Write as _;
+
+    let f = loads.force();
+    let BodyLoads {
+        pressure: fp,
+        viscous: fv,
+        moment: m,
+        cd,
+        cl,
+        cm,
+    } = loads;
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|_| FailedWriteFileSnafu {
+            path: path.to_path_buf(),
+        })?;
+    writeln!(
+        file,
+        "{step},{time:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{:.6e},{cd:.6e},{cl:.6e},{cm:.6e}",
+        f[0], f[1], f[2], fp[0], fp[1], fp[2], fv[0], fv[1], fv[2], m[0], m[1], m[2],
+    )
+    .with_context(|_| FailedWriteFileSnafu {
+        path: path.to_path_buf(),
+    })?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use utils::parameters::{BC, CheckpointConfig, Config, ModelScale, Resolution};
+use utils::parameters::{BC, CheckpointConfig, Config, ModelScale, Resolution, VelocityFunction};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub(crate) struct GuiConfig {
@@ -12,6 +12,8 @@ pub(crate) struct GuiConfig {
     // Boundary Condition
     pub bc_pattern: BC,
     pub u_lid: f64,
+    #[serde(default)]
+    pub u_lid_fn: VelocityFunction,
 
     // SPH parameters
     pub smooth_length: f64,
@@ -47,6 +49,7 @@ impl From<GuiConfig> for Config {
                 model_scale: gui_config.model_scale,
                 bc_pattern: gui_config.bc_pattern,
                 u_lid: gui_config.u_lid,
+                u_lid_fn: gui_config.u_lid_fn,
                 smooth_length: gui_config.smooth_length,
                 cell_scale: gui_config.cell_scale,
                 beta: gui_config.beta,
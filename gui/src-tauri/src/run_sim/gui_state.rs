@@ -3,6 +3,7 @@ use utils::{
     error::SimError,
     parameters::{DIM, Particle},
     rw_checkpoint::{State, load_data_from_checkpoint, read_checkpoint_and_set_buffer},
+    surface_mesh::TriangleMesh,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -10,14 +11,22 @@ pub struct GuiState<const D: usize> {
     pub particles: Vec<Particle<D>>,
     pub step: usize,
     pub time: f64,
+    /// Fluid surface (when `surface_mesh` is configured in the checkpoint)
+    pub surface: Option<TriangleMesh>,
 }
 
-impl<'a, const D: usize> From<State<'a, D>> for GuiState<D> {
-    fn from(value: State<'a, D>) -> Self {
+impl<'a> From<State<'a, DIM>> for GuiState<DIM> {
+    fn from(value: State<'a, DIM>) -> Self {
+        let surface = value
+            .checkpoint_config
+            .surface_mesh
+            .as_ref()
+            .map(|config| TriangleMesh::reconstruct(&value.particles, config));
         Self {
             particles: value.particles.into_owned(),
             step: value.step,
             time: value.time,
+            surface,
         }
     }
 }
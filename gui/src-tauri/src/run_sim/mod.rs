@@ -6,7 +6,7 @@ use tauri::Window;
 use utils::parameters::{Config, DIM};
 
 use gui_config::GuiConfig;
-use send_listen::{new_stop_listener, sender};
+use send_listen::{new_run_control, new_stop_listener, sender};
 
 use crate::run_sim::gui_state::GuiState;
 
@@ -14,6 +14,7 @@ use crate::run_sim::gui_state::GuiState;
 pub(crate) async fn run_simulation(window: Window, config: GuiConfig) -> Result<(), String> {
     let mut config: Config = config.into();
     config.log_report = Some(Box::new(sender(window.clone(), "terra://simulation-log")));
+    config.run_control = Some(new_run_control(&window));
     config.stop_step = Some(new_stop_listener(window));
 
     sph::sph::sph(config).map_err(|e| e.to_string())
@@ -1,5 +1,5 @@
 use tauri::{Emitter, Listener, Window};
-use utils::parameters::StopJudgeFn;
+use utils::parameters::{RunCommand, RunControl, StopJudgeFn};
 
 /// Create closure that reports.
 pub(crate) fn sender<S>(window: Window, event: &'static str) -> impl Fn(S) + Clone
@@ -26,3 +26,48 @@ pub(crate) fn new_stop_listener(window: Window) -> StopJudgeFn {
     let stop_flag = std::sync::Arc::clone(&stop_flag);
     Box::new(move |_step| stop_flag.load(std::sync::atomic::Ordering::Acquire))
 }
+
+/// Parameters changed mid-run (payload of `terra://simulation-params-event`)
+#[derive(serde::Deserialize)]
+struct LiveParameters {
+    out_step: Option<usize>,
+    u_lid: Option<f64>,
+    cs_rate: Option<f64>,
+}
+
+/// Forward the run-control events to the simulation.
+pub(crate) fn new_run_control(window: &Window) -> RunControl {
+    let (command_sender, receiver) = std::sync::mpsc::channel();
+    let events = [
+        ("terra://simulation-pause-event", RunCommand::Pause),
+        ("terra://simulation-resume-event", RunCommand::Resume),
+        ("terra://simulation-step-event", RunCommand::Step),
+        ("terra://simulation-checkpoint-event", RunCommand::WriteCheckpoint),
+    ];
+    for (event, command) in events {
+        let command_sender = command_sender.clone();
+        window.listen(event, move |_event| {
+            // The run has ended when the receiver is dropped
+            let _ = command_sender.send(command.clone());
+        });
+    }
+
+    window.listen("terra://simulation-params-event", move |event| {
+        let params: LiveParameters = match serde_json::from_str(event.payload()) {
+            Ok(params) => params,
+            Err(err) => {
+                println!("{err}");
+                return;
+            }
+        };
+        let commands = [
+            params.out_step.map(RunCommand::SetOutStep),
+            params.u_lid.map(RunCommand::SetLidVelocity),
+            params.cs_rate.map(RunCommand::SetSmoothingRate),
+        ];
+        for command in commands.into_iter().flatten() {
+            let _ = command_sender.send(command);
+        }
+    });
+    receiver
+}
@@ -23,6 +23,30 @@ export type ParticleLogNormalized =
         v: Vector3;
         dvdt: Vector3;
       };
+    }
+  | {
+      kind: "BodyForce";
+      data: {
+        step: number;
+        time: number;
+        loads: {
+          pressure: Vector3;
+          viscous: Vector3;
+          moment: Vector3;
+          cd: number;
+          cl: number;
+          cm: number;
+        };
+      };
+    }
+  | {
+      kind: "TimeStep";
+      data: {
+        step: number;
+        time: number;
+        dt: number;
+        criterion: "Acoustic" | "Viscous" | "Force" | "Thermal" | "Growth" | "Maximum";
+      };
     };
 
 /**
@@ -60,6 +84,23 @@ export const particleLogToString = (log: ParticleLogNormalized): string => {
       );
     }
 
+    case "BodyForce": {
+      const { step, time, loads } = log.data;
+      const f = loads.pressure.map((p, d) => p + loads.viscous[d]);
+
+      return (
+        `[Force] Step ${step}, time = ${f3(time * 1000)} [ms]\n` +
+        `    (Fx, Fy, Fz) = ${f3(f[0])}, ${f3(f[1])}, ${f3(f[2])}\n` +
+        `    (Mx, My, Mz) = ${f3(loads.moment[0])}, ${f3(loads.moment[1])}, ${f3(loads.moment[2])}\n` +
+        `    (Cd, Cl, Cm) = ${f3(loads.cd)}, ${f3(loads.cl)}, ${f3(loads.cm)}`
+      );
+    }
+
+    case "TimeStep": {
+      const { step, time, dt, criterion } = log.data;
+      return `[TimeStep] Step ${step}, time = ${f3(time * 1000)} [ms], dt = ${dt.toExponential(3)} [s] (${criterion})`;
+    }
+
     default: {
       const _exhaustive: never = log;
       return _exhaustive;
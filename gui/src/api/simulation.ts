@@ -1,4 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
+import { emit } from "@tauri-apps/api/event";
 import {
   type Config,
   type Matrix3,
@@ -13,20 +14,44 @@ export const runSimulation = async (config: Config) => {
   }
 };
 
-/** Fluid **/
-export const FLUID_OPTIONS = ["Water", "Air"] as const;
-export type FLUID = (typeof FLUID_OPTIONS)[number];
+export const stopSimulation = () => emit("terra://simulation-stop-event");
+export const pauseSimulation = () => emit("terra://simulation-pause-event");
+export const resumeSimulation = () => emit("terra://simulation-resume-event");
+/// Advance one step while paused
+export const stepSimulation = () => emit("terra://simulation-step-event");
+/// Write a checkpoint of the current state
+export const requestCheckpoint = () =>
+  emit("terra://simulation-checkpoint-event");
+
+/// Parameters changed mid-run (omitted ones are kept)
+export interface LiveParameters {
+  out_step?: number;
+  /// [m/s]
+  u_lid?: number;
+  cs_rate?: number;
+}
+
+export const setLiveParameters = (params: LiveParameters) =>
+  emit("terra://simulation-params-event", params);
 
 export interface Particle {
   // SPH parameters
   pair: number; // pair numbers per one particles
   volume: number; // [m^3]
+  /// smoothing length [m]
+  h: number;
+  /// refinement level (0: base resolution)
+  level: number;
+  /// grad-h correction term [-]
+  omega: number;
 
   // physical quantity for fluid
   /// initial density [kg/m^3]
   rho0: number;
   /// density [kg/m^3]
   rho: number;
+  /// density rate [kg/m^3/s]
+  drhodt: number;
   /// viscosity [Pa*s]
   viscosity: number;
   /// sound velocity [m/s]
@@ -45,14 +70,25 @@ export interface Particle {
   dedt: number;
   /// Temperature [K]
   temperature: number;
-  /// Fluid type (Water, Air, etc.)
-  fluid: FLUID;
+  /// on the free surface
+  surface: boolean;
+  /// outward unit normal of the free surface
+  normal: Vector3;
+  /// Material (index into the material library)
+  material: number;
+}
+
+export interface SurfaceMesh {
+  vertices: Vector3[];
+  triangles: [number, number, number][];
 }
 
 export interface GuiState {
   particles: Particle[];
   step: number;
   time: number;
+  /// fluid surface (when surface_mesh is configured)
+  surface: SurfaceMesh | null;
 }
 
 export const loadParticleState = async (
@@ -1,6 +1,7 @@
 import { useParameters } from "./providers/parameters/useParameters";
 import { BC_OPTIONS, type BC } from "./providers/parameters/types";
 import * as React from "react";
+import { setLiveParameters } from "../api/simulation";
 
 /* -----------------------------
  * Small reusable helpers
@@ -153,8 +154,34 @@ const BoundaryConditions: React.FC = () => {
       <NumberField
         label="U_lid [m/s]:"
         value={state.u_lid}
-        onChange={(v) => dispatch({ type: "SET_U_LID", value: v })}
+        onChange={(v) => {
+          dispatch({ type: "SET_U_LID", value: v });
+          if (state.isRunning) void setLiveParameters({ u_lid: v });
+        }}
       />
+
+      <label style={{ display: "block", marginBottom: "6px" }}>
+        U_lid(t, x, y, z, u) expression:
+        <input
+          type="text"
+          placeholder="u (constant), e.g. u*min(t/0.1,1)"
+          value={
+            typeof state.u_lid_fn === "object" && "Expression" in state.u_lid_fn
+              ? state.u_lid_fn.Expression
+              : ""
+          }
+          onChange={(e) =>
+            dispatch({
+              type: "SET_U_LID_FN",
+              value:
+                e.target.value.trim() === ""
+                  ? "Constant"
+                  : { Expression: e.target.value },
+            })
+          }
+          style={{ width: "100%" }}
+        />
+      </label>
     </>
   );
 };
@@ -202,12 +229,13 @@ const SPHParameters: React.FC = () => {
       <NumberField
         label="CS_RATE:"
         value={state.cs_rate}
-        onChange={(v) =>
+        onChange={(v) => {
           dispatch({
             type: "SET_SPH_PARAMS",
             value: { cs_rate: v },
-          })
-        }
+          });
+          if (state.isRunning) void setLiveParameters({ cs_rate: v });
+        }}
       />
     </>
   );
@@ -277,12 +305,13 @@ const SimulationParameters: React.FC = () => {
       <NumberField
         label="Step to display:"
         value={state.out_step}
-        onChange={(v) =>
+        onChange={(v) => {
           dispatch({
             type: "SET_TIME_STEPPING",
             value: { out_step: v },
-          })
-        }
+          });
+          if (state.isRunning) void setLiveParameters({ out_step: v });
+        }}
       />
 
       <NumberField
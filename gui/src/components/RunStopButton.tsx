@@ -1,12 +1,20 @@
-import { emit } from "@tauri-apps/api/event";
 import { useParameters } from "./providers/parameters/useParameters";
-import { useCallback } from "react";
-import { runSimulation } from "../api/simulation";
+import { useCallback, useState } from "react";
+import {
+  pauseSimulation,
+  requestCheckpoint,
+  resumeSimulation,
+  runSimulation,
+  stepSimulation,
+  stopSimulation,
+} from "../api/simulation";
 
 export const RunStopButton: React.FC = () => {
   const { state, dispatch } = useParameters();
+  const [isPaused, setIsPaused] = useState(false);
 
   const handleRun = useCallback(async () => {
+    setIsPaused(false);
     dispatch({
       type: "SET_IS_RUNNING",
       value: true,
@@ -23,21 +31,47 @@ export const RunStopButton: React.FC = () => {
   }, [dispatch, state]);
 
   const handleStop = async () => {
-    await emit("terra://simulation-stop-event");
+    await stopSimulation();
     dispatch({
       type: "SET_IS_RUNNING",
       value: false,
     });
   };
 
-  return !state.isRunning ? (
-    <button style={runStyle} onClick={handleRun}>
-      Run
-    </button>
-  ) : (
-    <button style={stopStyle} onClick={handleStop}>
-      Stop
-    </button>
+  const handlePause = async () => {
+    await (isPaused ? resumeSimulation() : pauseSimulation());
+    setIsPaused(!isPaused);
+  };
+
+  if (!state.isRunning) {
+    return (
+      <button style={runStyle} onClick={handleRun}>
+        Run
+      </button>
+    );
+  }
+
+  return (
+    <>
+      <button style={stopStyle} onClick={handleStop}>
+        Stop
+      </button>
+      <div style={controlsStyle}>
+        <button style={controlStyle} onClick={handlePause}>
+          {isPaused ? "Resume" : "Pause"}
+        </button>
+        <button
+          style={controlStyle}
+          onClick={stepSimulation}
+          disabled={!isPaused}
+        >
+          Step
+        </button>
+        <button style={controlStyle} onClick={requestCheckpoint}>
+          Checkpoint
+        </button>
+      </div>
+    </>
   );
 };
 
@@ -54,3 +88,14 @@ const stopStyle: React.CSSProperties = {
   ...runStyle,
   background: "#aa0025",
 };
+
+const controlsStyle: React.CSSProperties = {
+  display: "flex",
+  gap: "4px",
+  marginBottom: "10px",
+};
+
+const controlStyle: React.CSSProperties = {
+  flex: 1,
+  padding: "4px",
+};
@@ -2,7 +2,7 @@ import { useEffect, useRef } from "react";
 import * as THREE from "three";
 import { OrbitControls } from "three/examples/jsm/controls/OrbitControls";
 import { useParameters } from "./providers/parameters/useParameters";
-import type { Particle } from "../api/simulation";
+import type { Particle, SurfaceMesh } from "../api/simulation";
 
 const createScene = () => {
   const scene = new THREE.Scene();
@@ -140,6 +140,29 @@ const createParticles = (
   return new THREE.Points(geometry, material);
 };
 
+const createSurface = (surface: SurfaceMesh) => {
+  const geometry = new THREE.BufferGeometry();
+
+  const positions = new Float32Array(surface.vertices.length * 3);
+  surface.vertices.forEach((v, i) => {
+    positions[i * 3 + 0] = v[0] - 0.25;
+    positions[i * 3 + 1] = v[1] - 0.25;
+    positions[i * 3 + 2] = v[2] - 0.25;
+  });
+
+  geometry.setAttribute("position", new THREE.BufferAttribute(positions, 3));
+  geometry.setIndex(surface.triangles.flat());
+  geometry.computeVertexNormals();
+
+  const material = new THREE.MeshNormalMaterial({
+    transparent: true,
+    opacity: 0.6,
+    side: THREE.DoubleSide,
+  });
+
+  return new THREE.Mesh(geometry, material);
+};
+
 /* ---------- component ---------- */
 
 export const ThreeCanvas: React.FC = () => {
@@ -179,6 +202,11 @@ export const ThreeCanvas: React.FC = () => {
     );
     scene.add(particles);
 
+    // Fluid surface follows the particle rotation
+    if (state.guiState?.surface) {
+      particles.add(createSurface(state.guiState.surface));
+    }
+
     const animate = () => {
       requestAnimationFrame(animate);
       controls.update();
@@ -13,6 +13,7 @@ export const INITIAL_PARAMETER_STATE = {
 
   bc_pattern: "Cavity-Flow",
   u_lid: 5.0,
+  u_lid_fn: "Constant",
 
   smooth_length: 0.0324,
   cell_scale: 2.0,
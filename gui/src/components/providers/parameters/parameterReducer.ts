@@ -27,6 +27,9 @@ export const parameterReducer = (
     case "SET_U_LID":
       return { ...state, u_lid: action.value };
 
+    case "SET_U_LID_FN":
+      return { ...state, u_lid_fn: action.value };
+
     case "SET_SPH_PARAMS":
       return {
         ...state,
@@ -30,6 +30,14 @@ export const BC_OPTIONS = [
 
 export type BC = (typeof BC_OPTIONS)[number];
 
+/** Time/position dependence of a boundary velocity (`u` = configured velocity) **/
+export type VelocityFunction =
+  | "Constant"
+  | { Ramp: { duration: number } }
+  | { Sine: { frequency: number; phase: number } }
+  | { Table: { path: string } }
+  | { Expression: string };
+
 export interface Config {
   // Max particles
   max_n: number;
@@ -41,6 +49,7 @@ export interface Config {
   // Boundary condition
   bc_pattern: BC;
   u_lid: number;
+  u_lid_fn: VelocityFunction;
 
   // SPH parameters
   smooth_length: number;
@@ -74,6 +83,7 @@ export type ParameterAction =
   | { type: "SET_MODEL_SCALE"; value: ModelScale }
   | { type: "SET_BC_PATTERN"; value: BC }
   | { type: "SET_U_LID"; value: number }
+  | { type: "SET_U_LID_FN"; value: VelocityFunction }
   | {
       type: "SET_SPH_PARAMS";
       value: Partial<